@@ -1,4 +1,9 @@
+use std::{env, path::PathBuf};
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_build::compile_protos("proto/user.proto")?;
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+    tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("user_search_descriptor.bin"))
+        .compile(&["proto/user.proto"], &["proto"])?;
     Ok(())
 }
\ No newline at end of file
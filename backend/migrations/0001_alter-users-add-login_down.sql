@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS users_login_lower_idx;
+ALTER TABLE users DROP COLUMN IF EXISTS login;
\ No newline at end of file
@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS login VARCHAR(254);
+CREATE UNIQUE INDEX IF NOT EXISTS users_login_lower_idx ON users (lower(login));
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS friend_counts;
\ No newline at end of file
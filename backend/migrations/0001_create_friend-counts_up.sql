@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS friend_counts (
+  user_id UUID PRIMARY KEY,
+  followers_count BIGINT NOT NULL DEFAULT 0,
+  following_count BIGINT NOT NULL DEFAULT 0
+);
+INSERT INTO friend_counts (user_id, following_count)
+  SELECT user_id, COUNT(*) FROM friends GROUP BY user_id
+  ON CONFLICT (user_id) DO UPDATE SET following_count = EXCLUDED.following_count;
+INSERT INTO friend_counts (user_id, followers_count)
+  SELECT friend_id, COUNT(*) FROM friends GROUP BY friend_id
+  ON CONFLICT (user_id) DO UPDATE SET followers_count = EXCLUDED.followers_count;
\ No newline at end of file
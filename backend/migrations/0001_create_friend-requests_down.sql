@@ -0,0 +1 @@
+DROP TABLE IF EXISTS friend_requests;
\ No newline at end of file
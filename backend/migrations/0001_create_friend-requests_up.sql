@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS friend_requests (
+  id UUID DEFAULT uuid_generate_v4(),
+  from_user_id UUID NOT NULL,
+  to_user_id UUID NOT NULL,
+  status VARCHAR(16) NOT NULL DEFAULT 'pending',
+  time_created TIMESTAMP NOT NULL DEFAULT NOW(),
+  time_updated TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX IF NOT EXISTS friend_requests_id_idx ON friend_requests (id);
+CREATE UNIQUE INDEX IF NOT EXISTS friend_requests_pending_idx ON friend_requests (from_user_id, to_user_id) WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS friend_requests_to_user_id_idx ON friend_requests (to_user_id, time_created);
\ No newline at end of file
@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS friends_id_idx ON friends (id);
\ No newline at end of file
@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS sessions_id_idx ON sessions (id);
\ No newline at end of file
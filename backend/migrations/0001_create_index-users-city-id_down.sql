@@ -0,0 +1 @@
+DROP INDEX IF EXISTS users_city_id_idx;
\ No newline at end of file
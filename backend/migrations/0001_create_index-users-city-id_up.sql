@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS users_city_id_idx ON users (city, id);
\ No newline at end of file
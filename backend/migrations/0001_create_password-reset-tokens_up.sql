@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS password_reset_tokens (
+  id UUID DEFAULT uuid_generate_v4(),
+  user_id UUID NOT NULL,
+  token_hash VARCHAR(128) NOT NULL,
+  time_created TIMESTAMP NOT NULL DEFAULT NOW(),
+  time_expires TIMESTAMP NOT NULL,
+  time_used TIMESTAMP
+);
+CREATE UNIQUE INDEX IF NOT EXISTS password_reset_tokens_id_idx ON password_reset_tokens (id);
+CREATE INDEX IF NOT EXISTS password_reset_tokens_user_id_idx ON password_reset_tokens (user_id);
\ No newline at end of file
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS user_blocks (
+  id UUID DEFAULT uuid_generate_v4(),
+  user_id UUID NOT NULL,
+  blocked_user_id UUID NOT NULL,
+  time_created TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX IF NOT EXISTS user_blocks_id_idx ON user_blocks (id);
+CREATE UNIQUE INDEX IF NOT EXISTS user_blocks_user_id_blocked_user_id_idx ON user_blocks (user_id, blocked_user_id);
+CREATE INDEX IF NOT EXISTS user_blocks_blocked_user_id_idx ON user_blocks (blocked_user_id);
\ No newline at end of file
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS user_deletions (
+  user_id UUID NOT NULL,
+  steps_done TEXT[] NOT NULL DEFAULT '{}',
+  attempts INTEGER NOT NULL DEFAULT 0,
+  last_error TEXT,
+  time_created TIMESTAMP NOT NULL DEFAULT NOW(),
+  time_locked_until TIMESTAMP,
+  time_finished TIMESTAMP
+);
+CREATE UNIQUE INDEX IF NOT EXISTS user_deletions_user_id_idx ON user_deletions (user_id);
+CREATE INDEX IF NOT EXISTS user_deletions_pending_idx ON user_deletions (time_created) WHERE time_finished IS NULL;
\ No newline at end of file
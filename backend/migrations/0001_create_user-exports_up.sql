@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS user_exports (
+  id UUID DEFAULT uuid_generate_v4(),
+  user_id UUID NOT NULL,
+  archive BYTEA,
+  error TEXT,
+  time_created TIMESTAMP NOT NULL DEFAULT NOW(),
+  time_finished TIMESTAMP,
+  time_expires TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX IF NOT EXISTS user_exports_id_idx ON user_exports (id);
+CREATE INDEX IF NOT EXISTS user_exports_user_id_idx ON user_exports (user_id, time_created);
\ No newline at end of file
@@ -0,0 +1,77 @@
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::session::Session;
+
+lazy_static! {
+    pub static ref ACCESS_TOKEN_SECRET: Option<String> = std::env::var("ACCESS_TOKEN_SECRET").ok().filter(|secret| !secret.is_empty());
+    pub static ref ACCESS_TOKEN_TTL_SECONDS: i64 = std::env::var("ACCESS_TOKEN_TTL_SECONDS").unwrap_or_else(|_| "900".to_string()).parse::<i64>().unwrap_or(900);
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccessTokenClaims {
+    sub: Uuid,
+    sid: Uuid,
+    iat: i64,
+    exp: i64,
+}
+
+// Signed tokens are optional: without ACCESS_TOKEN_SECRET only session ids are issued
+pub fn issue(session: &Session) -> Option<String> {
+    issue_for(&session.get_user_id(), &session.get_id())
+}
+
+fn issue_for(user_id: &Uuid, session_id: &Uuid) -> Option<String> {
+    let secret = ACCESS_TOKEN_SECRET.as_ref()?;
+    let now = chrono::Utc::now().timestamp();
+    let claims = AccessTokenClaims {
+        sub: *user_id,
+        sid: *session_id,
+        iat: now,
+        exp: now + *ACCESS_TOKEN_TTL_SECONDS,
+    };
+
+    match encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes())) {
+        Ok(token) => Some(token),
+        Err(err) => {
+            log::error!("unable to issue access token: {:?}", err);
+            None
+        }
+    }
+}
+// Returns the user the token was issued to, None for anything that is not a valid unexpired token
+pub fn verify(token: &str) -> Option<Uuid> {
+    let secret = ACCESS_TOKEN_SECRET.as_ref()?;
+    match decode::<AccessTokenClaims>(token, &DecodingKey::from_secret(secret.as_bytes()), &Validation::new(Algorithm::HS256)) {
+        Ok(token_data) => Some(token_data.claims.sub),
+        Err(err) => {
+            log::debug!("access token is invalid: {:?}", err);
+            None
+        }
+    }
+}
+
+// Authorization headers for requests proxied to the dialogs and unread services
+pub fn get_headers(session: &Session) -> reqwest::header::HeaderMap {
+    to_headers(issue(session))
+}
+
+// Background jobs act on behalf of a user without a session, the token carries a nil session id
+pub fn get_headers_for_user(user_id: &Uuid) -> reqwest::header::HeaderMap {
+    to_headers(issue_for(user_id, &Uuid::nil()))
+}
+
+fn to_headers(token: Option<String>) -> reqwest::header::HeaderMap {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(token) = token {
+        match reqwest::header::HeaderValue::from_str(("Bearer ".to_owned() + token.as_str()).as_str()) {
+            Ok(value) => {
+                headers.insert(reqwest::header::AUTHORIZATION, value);
+            }
+            Err(err) => log::error!("unable to build authorization header: {:?}", err),
+        }
+    }
+    headers
+}
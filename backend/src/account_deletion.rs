@@ -0,0 +1,230 @@
+use std::time::Duration;
+use futures::io;
+use lazy_static::lazy_static;
+use tokio_postgres::{Error as PostgresError, GenericClient};
+use uuid::Uuid;
+
+use crate::access_token;
+use crate::friend::Friend;
+use crate::friend_request::FriendRequest;
+use crate::friend_suggestion;
+use crate::post;
+use crate::postgres;
+use crate::redis;
+use crate::session::Session;
+use crate::user_block::UserBlock;
+
+lazy_static! {
+    pub static ref USER_DELETION_RETRY_INTERVAL_SECONDS: u64 = std::env::var("USER_DELETION_RETRY_INTERVAL_SECONDS").unwrap_or_else(|_| "30".to_string()).parse::<u64>().unwrap_or(30);
+    pub static ref USER_DELETION_LOCK_SECONDS: i64 = std::env::var("USER_DELETION_LOCK_SECONDS").unwrap_or_else(|_| "300".to_string()).parse::<i64>().unwrap_or(300);
+    pub static ref USER_DELETION_BATCH_SIZE: i64 = std::env::var("USER_DELETION_BATCH_SIZE").unwrap_or_else(|_| "10".to_string()).parse::<i64>().unwrap_or(10);
+}
+
+// Cleanup outside of the main database, in execution order. Feed caches go before friends
+// since the list of followers is taken from the friend storage
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DeletionStep {
+    Sessions,
+    FeedCaches,
+    Friends,
+    Dialogs,
+    Unread,
+}
+
+const STEPS: [DeletionStep; 5] = [
+    DeletionStep::Sessions,
+    DeletionStep::FeedCaches,
+    DeletionStep::Friends,
+    DeletionStep::Dialogs,
+    DeletionStep::Unread,
+];
+
+impl DeletionStep {
+    fn get_name(&self) -> &'static str {
+        match self {
+            DeletionStep::Sessions => "sessions",
+            DeletionStep::FeedCaches => "feed_caches",
+            DeletionStep::Friends => "friends",
+            DeletionStep::Dialogs => "dialogs",
+            DeletionStep::Unread => "unread",
+        }
+    }
+
+    async fn execute(&self, user_id: &Uuid) -> Result<(), io::Error> {
+        match self {
+            DeletionStep::Sessions => {
+                Session::delete_by_user_id(user_id).await?;
+                Ok(())
+            }
+            DeletionStep::FeedCaches => purge_feed_caches(user_id).await,
+            DeletionStep::Friends => delete_friends(user_id).await,
+            DeletionStep::Dialogs => {
+                let url = std::env::var("DIALOGS_SERVICE_URL").unwrap_or_else(|_| String::from("dialogs:8001"));
+                remove_messages(url, user_id).await
+            }
+            DeletionStep::Unread => {
+                let url = std::env::var("UNREAD_SERVICE_URL").unwrap_or_else(|_| String::from("unread:8001"));
+                remove_messages(url, user_id).await
+            }
+        }
+    }
+}
+
+// Removes the profile, posts, reset tokens and exports at once and queues the cleanup of the other stores
+pub async fn schedule<C: GenericClient>(client: &mut C, user_id: &Uuid) -> Result<(), PostgresError> {
+    let transaction = client.transaction().await?;
+    transaction.execute("DELETE FROM posts WHERE user_id = $1", &[user_id]).await?;
+    transaction.execute("DELETE FROM password_reset_tokens WHERE user_id = $1", &[user_id]).await?;
+    transaction.execute("DELETE FROM user_exports WHERE user_id = $1", &[user_id]).await?;
+    transaction.execute("DELETE FROM users WHERE id = $1", &[user_id]).await?;
+    transaction.execute(
+        "INSERT INTO user_deletions (user_id) VALUES ($1) ON CONFLICT (user_id) DO NOTHING",
+        &[user_id],
+    ).await?;
+    transaction.commit().await
+}
+
+// Claims pending deletions for USER_DELETION_LOCK_SECONDS so that instances do not process the same user twice
+async fn claim<C: GenericClient>(client: &C, user_id: Option<&Uuid>) -> Result<Vec<(Uuid, Vec<String>)>, PostgresError> {
+    let stmt = client.prepare(
+        "UPDATE user_deletions SET time_locked_until = NOW() + make_interval(secs => $2), attempts = attempts + 1 \
+        WHERE user_id IN ( \
+            SELECT user_id FROM user_deletions \
+            WHERE time_finished IS NULL \
+            AND (time_locked_until IS NULL OR time_locked_until < NOW()) \
+            AND ($1::uuid IS NULL OR user_id = $1) \
+            ORDER BY time_created LIMIT $3 \
+            FOR UPDATE SKIP LOCKED \
+        ) \
+        RETURNING user_id, steps_done"
+    ).await?;
+    let rows = client.query(&stmt, &[&user_id, &(*USER_DELETION_LOCK_SECONDS as f64), &*USER_DELETION_BATCH_SIZE]).await?;
+    Ok(rows.into_iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+async fn mark_step_done<C: GenericClient>(client: &C, user_id: &Uuid, step: &DeletionStep) -> Result<(), PostgresError> {
+    client.execute(
+        "UPDATE user_deletions SET steps_done = array_append(steps_done, $2) WHERE user_id = $1 AND NOT ($2 = ANY(steps_done))",
+        &[user_id, &step.get_name()],
+    ).await?;
+    Ok(())
+}
+
+async fn mark_failed<C: GenericClient>(client: &C, user_id: &Uuid, error: &str) -> Result<(), PostgresError> {
+    client.execute(
+        "UPDATE user_deletions SET last_error = $2, time_locked_until = NULL WHERE user_id = $1",
+        &[user_id, &error],
+    ).await?;
+    Ok(())
+}
+
+async fn mark_finished<C: GenericClient>(client: &C, user_id: &Uuid) -> Result<(), PostgresError> {
+    client.execute(
+        "UPDATE user_deletions SET time_finished = NOW(), last_error = NULL, time_locked_until = NULL WHERE user_id = $1",
+        &[user_id],
+    ).await?;
+    Ok(())
+}
+
+// Runs the steps that are not recorded as done yet and stops at the first failure,
+// the remaining steps are retried by the background task
+async fn process_claimed<C: GenericClient>(client: &C, user_id: &Uuid, steps_done: &[String]) -> Result<(), PostgresError> {
+    for step in STEPS.iter() {
+        if steps_done.iter().any(|name| name == step.get_name()) {
+            continue;
+        }
+        match step.execute(user_id).await {
+            Ok(_) => mark_step_done(client, user_id, step).await?,
+            Err(err) => {
+                log::warn!("user '{}' deletion step '{}' failed: {:?}", user_id, step.get_name(), err);
+                return mark_failed(client, user_id, &format!("{}: {}", step.get_name(), err)).await;
+            }
+        }
+    }
+    log::info!("user '{}' is deleted", user_id);
+    mark_finished(client, user_id).await
+}
+
+async fn process(user_id: Option<&Uuid>) -> Result<(), PostgresError> {
+    let client = match postgres::get_master_pool_ref().get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get postgres client: {:?}", err);
+            return Ok(());
+        }
+    };
+
+    for (user_id, steps_done) in claim(&**client, user_id).await? {
+        process_claimed(&**client, &user_id, &steps_done).await?;
+    }
+    Ok(())
+}
+
+// Starts the cleanup right away instead of waiting for the next background run
+pub fn process_now(user_id: Uuid) {
+    tokio::spawn(async move {
+        if let Err(err) = process(Some(&user_id)).await {
+            log::warn!("unable to process user '{}' deletion: {:?}", user_id, err);
+        }
+    });
+}
+
+pub fn spawn() {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(*USER_DELETION_RETRY_INTERVAL_SECONDS)).await;
+            if let Err(err) = process(None).await {
+                log::warn!("unable to process pending user deletions: {:?}", err);
+            }
+        }
+    });
+}
+
+// Followers keep the user's posts in their cached feeds
+async fn purge_feed_caches(user_id: &Uuid) -> Result<(), io::Error> {
+    let mut redis_connection = redis::get_pool_ref().get().await.map_err(io::Error::other)?;
+
+    let followers = Friend::get_by_friend_id(user_id).await?;
+    for friend in followers.iter().map(|friend| friend.get_user_id()).chain(std::iter::once(*user_id)) {
+        let cache_key = post::FEED_CACHE_KEY_PREFIX.to_string() + friend.to_string().as_str();
+        redis::del(&cache_key, &mut redis_connection).await.map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+async fn delete_friends(user_id: &Uuid) -> Result<(), io::Error> {
+    let mut redis_connection = redis::get_pool_ref().get().await.map_err(io::Error::other)?;
+    friend_suggestion::invalidate(&mut redis_connection, &[*user_id]).await?;
+
+    let mut friends = Friend::get_by_user_id(user_id).await?;
+    friends.extend(Friend::get_by_friend_id(user_id).await?);
+    for friend in friends.iter() {
+        Friend::delete_relation(friend).await?;
+    }
+    FriendRequest::delete_by_user_id(user_id).await?;
+    UserBlock::delete_by_user_id(user_id).await?;
+    Ok(())
+}
+
+// The services only accept this call with a signed token, without ACCESS_TOKEN_SECRET the messages stay
+async fn remove_messages(service_url: String, user_id: &Uuid) -> Result<(), io::Error> {
+    if access_token::ACCESS_TOKEN_SECRET.is_none() {
+        log::warn!("messages of deleted user '{}' are kept in '{}': ACCESS_TOKEN_SECRET is not set", user_id, service_url);
+        return Ok(());
+    }
+
+    let response = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .map_err(io::Error::other)?
+        .post(service_url + "/user/remove")
+        .headers(access_token::get_headers_for_user(user_id))
+        .send()
+        .await
+        .map_err(io::Error::other)?;
+
+    if !response.status().is_success() {
+        return Err(io::Error::other(format!("service responded with {}", response.status())));
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use actix_web::{dev::Payload, error, Error, FromRequest, HttpRequest};
+use actix_web_httpauth::extractors::bearer::{self, BearerAuth};
+use actix_web_httpauth::extractors::AuthenticationError;
+use futures::future::LocalBoxFuture;
+use uuid::Uuid;
+
+use crate::access_token;
+use crate::session::Session;
+
+pub struct AuthenticatedUser {
+    session: Session,
+}
+
+impl AuthenticatedUser {
+    pub fn get_user_id(&self) -> Uuid {
+        self.session.get_user_id()
+    }
+
+    pub fn get_session_id(&self) -> Uuid {
+        self.session.get_id()
+    }
+
+    pub fn get_session(&self) -> &Session {
+        &self.session
+    }
+
+    pub fn is_owner_of(&self, user_id: &Uuid) -> bool {
+        self.get_user_id() == *user_id
+    }
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let bearer_auth = BearerAuth::from_request(req, payload);
+        let config = req.app_data::<bearer::Config>().cloned().unwrap_or_default();
+
+        Box::pin(async move {
+            let auth = bearer_auth.await?;
+            match Session::get_by_id(auth.token()).await {
+                Ok(Some(session)) => Ok(AuthenticatedUser { session }),
+                Ok(None) => {
+                    log::debug!("unauthorized: session is not found or expired");
+                    Err(AuthenticationError::from(config)
+                        .with_error(bearer::Error::InvalidToken)
+                        .into())
+                }
+                Err(err) => {
+                    log::debug!("unable to fetch session: {:?}", err);
+                    Err(error::ErrorInternalServerError("unable to fetch session"))
+                }
+            }
+        })
+    }
+}
+
+// gRPC callers pass the same credentials in the `authorization` metadata: either a signed access token
+// or a session id, both as "Bearer <token>"
+pub async fn authenticate_grpc<T>(request: &tonic::Request<T>) -> Result<Uuid, tonic::Status> {
+    let token = match request.metadata().get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        Some(token) => token,
+        None => {
+            log::debug!("unauthenticated: authorization metadata is missing");
+            return Err(tonic::Status::unauthenticated("authorization metadata is missing"));
+        }
+    };
+
+    if let Some(user_id) = access_token::verify(token) {
+        return Ok(user_id);
+    }
+
+    match Session::get_by_id(token).await {
+        Ok(Some(session)) => Ok(session.get_user_id()),
+        Ok(None) => {
+            log::debug!("unauthenticated: session is not found or expired");
+            Err(tonic::Status::unauthenticated("session is not found or expired"))
+        }
+        Err(err) => {
+            log::debug!("unable to fetch session: {:?}", err);
+            Err(tonic::Status::unavailable("unable to fetch session"))
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::sync::Arc;
+use futures::io;
+use tonic::async_trait;
+use uuid::Uuid;
+
+use crate::friend::{Friend, FriendCounts};
+use crate::friend_request::FriendRequest;
+use crate::friend_storage::FriendStorage;
+use crate::user_block::UserBlock;
+
+// Same contract as DualWriteSessionStorage: the source is authoritative, the target is best effort
+pub struct DualWriteFriendStorage {
+    source: Arc<dyn FriendStorage + Send + Sync>,
+    target: Arc<dyn FriendStorage + Send + Sync>,
+    is_read_from_target: bool,
+}
+
+impl DualWriteFriendStorage {
+    pub fn new(
+        source: Arc<dyn FriendStorage + Send + Sync>,
+        target: Arc<dyn FriendStorage + Send + Sync>,
+        is_read_from_target: bool,
+    ) -> Self {
+        Self {
+            source,
+            target,
+            is_read_from_target,
+        }
+    }
+
+    fn get_read_storage(&self) -> &Arc<dyn FriendStorage + Send + Sync> {
+        if self.is_read_from_target { &self.target } else { &self.source }
+    }
+}
+
+fn log_target_error<T>(operation: &str, result: Result<T, io::Error>) {
+    if let Err(err) = result {
+        log::warn!("unable to {} friend in migration target: {:?}", operation, err);
+    }
+}
+
+#[async_trait]
+impl FriendStorage for DualWriteFriendStorage {
+    async fn get_by_id(&self, id: &Uuid) -> Result<Option<Friend>, io::Error> {
+        self.get_read_storage().get_by_id(id).await
+    }
+
+    async fn get_by_user_id(&self, user_id: &Uuid) -> Result<Vec<Friend>, io::Error> {
+        self.get_read_storage().get_by_user_id(user_id).await
+    }
+
+    async fn get_by_friend_id(&self, friend_id: &Uuid) -> Result<Vec<Friend>, io::Error> {
+        self.get_read_storage().get_by_friend_id(friend_id).await
+    }
+
+    async fn get_by_user_ids(&self, user_ids: &[Uuid]) -> Result<Vec<Friend>, io::Error> {
+        self.get_read_storage().get_by_user_ids(user_ids).await
+    }
+
+    async fn get_by_friend_ids(&self, friend_ids: &[Uuid]) -> Result<Vec<Friend>, io::Error> {
+        self.get_read_storage().get_by_friend_ids(friend_ids).await
+    }
+
+    async fn get_by_user_id_and_friend_id(&self, user_id: &Uuid, friend_id: &Uuid) -> Result<Option<Friend>, io::Error> {
+        self.get_read_storage().get_by_user_id_and_friend_id(user_id, friend_id).await
+    }
+
+    async fn get_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Friend>, io::Error> {
+        self.get_read_storage().get_batch(after_id, limit).await
+    }
+
+    async fn create(&self, friend: &Friend) -> Result<Uuid, io::Error> {
+        let id = self.source.create(friend).await?;
+        log_target_error("create", self.target.create(friend).await);
+
+        Ok(id)
+    }
+
+    async fn delete(&self, friend: &Friend) -> Result<bool, io::Error> {
+        let is_deleted = self.source.delete(friend).await?;
+        log_target_error("delete", self.target.delete(friend).await);
+
+        Ok(is_deleted)
+    }
+
+    async fn is_persistant(&self, friend: &Friend) -> Result<bool, io::Error> {
+        self.get_read_storage().is_persistant(friend).await
+    }
+
+    async fn get_counts(&self, user_id: &Uuid) -> Result<FriendCounts, io::Error> {
+        self.get_read_storage().get_counts(user_id).await
+    }
+
+    async fn get_request_by_id(&self, id: &Uuid) -> Result<Option<FriendRequest>, io::Error> {
+        self.get_read_storage().get_request_by_id(id).await
+    }
+
+    async fn get_pending_request(&self, from_user_id: &Uuid, to_user_id: &Uuid) -> Result<Option<FriendRequest>, io::Error> {
+        self.get_read_storage().get_pending_request(from_user_id, to_user_id).await
+    }
+
+    async fn get_pending_requests_by_to_user_id(&self, to_user_id: &Uuid) -> Result<Vec<FriendRequest>, io::Error> {
+        self.get_read_storage().get_pending_requests_by_to_user_id(to_user_id).await
+    }
+
+    async fn get_pending_requests_by_from_user_id(&self, from_user_id: &Uuid) -> Result<Vec<FriendRequest>, io::Error> {
+        self.get_read_storage().get_pending_requests_by_from_user_id(from_user_id).await
+    }
+
+    async fn create_request(&self, request: &FriendRequest) -> Result<Uuid, io::Error> {
+        let id = self.source.create_request(request).await?;
+        log_target_error("create request for", self.target.create_request(request).await);
+
+        Ok(id)
+    }
+
+    async fn update_request_status(&self, request: &FriendRequest) -> Result<bool, io::Error> {
+        let is_updated = self.source.update_request_status(request).await?;
+        if is_updated {
+            log_target_error("update request for", self.target.update_request_status(request).await);
+        }
+
+        Ok(is_updated)
+    }
+
+    async fn accept_request(&self, request: &FriendRequest, friends: &[Friend]) -> Result<bool, io::Error> {
+        let is_accepted = self.source.accept_request(request, friends).await?;
+        if is_accepted {
+            log_target_error("accept request for", self.target.accept_request(request, friends).await);
+        }
+
+        Ok(is_accepted)
+    }
+
+    async fn delete_requests_by_user_id(&self, user_id: &Uuid) -> Result<u64, io::Error> {
+        let deleted_count = self.source.delete_requests_by_user_id(user_id).await?;
+        log_target_error("delete requests for", self.target.delete_requests_by_user_id(user_id).await);
+
+        Ok(deleted_count)
+    }
+
+    async fn get_block(&self, user_id: &Uuid, blocked_user_id: &Uuid) -> Result<Option<UserBlock>, io::Error> {
+        self.get_read_storage().get_block(user_id, blocked_user_id).await
+    }
+
+    async fn get_blocks_by_user_id(&self, user_id: &Uuid) -> Result<Vec<UserBlock>, io::Error> {
+        self.get_read_storage().get_blocks_by_user_id(user_id).await
+    }
+
+    async fn get_blocks_by_blocked_user_id(&self, blocked_user_id: &Uuid) -> Result<Vec<UserBlock>, io::Error> {
+        self.get_read_storage().get_blocks_by_blocked_user_id(blocked_user_id).await
+    }
+
+    async fn create_block(&self, block: &UserBlock) -> Result<Uuid, io::Error> {
+        let id = self.source.create_block(block).await?;
+        log_target_error("create block for", self.target.create_block(block).await);
+
+        Ok(id)
+    }
+
+    async fn delete_block(&self, user_id: &Uuid, blocked_user_id: &Uuid) -> Result<bool, io::Error> {
+        let is_deleted = self.source.delete_block(user_id, blocked_user_id).await?;
+        log_target_error("delete block for", self.target.delete_block(user_id, blocked_user_id).await);
+
+        Ok(is_deleted)
+    }
+
+    async fn delete_blocks_by_user_id(&self, user_id: &Uuid) -> Result<u64, io::Error> {
+        let deleted_count = self.source.delete_blocks_by_user_id(user_id).await?;
+        log_target_error("delete blocks for", self.target.delete_blocks_by_user_id(user_id).await);
+
+        Ok(deleted_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_friend_storage::MemoryFriendStorage;
+
+    #[tokio::test]
+    async fn writes_reach_both_storages() {
+        let source: Arc<dyn FriendStorage + Send + Sync> = Arc::new(MemoryFriendStorage::new());
+        let target: Arc<dyn FriendStorage + Send + Sync> = Arc::new(MemoryFriendStorage::new());
+        let storage = DualWriteFriendStorage::new(source.clone(), target.clone(), true);
+        let friend = Friend::new(None, Uuid::new_v4(), Uuid::new_v4());
+        let other_friend = Friend::new(None, Uuid::new_v4(), friend.get_user_id());
+
+        storage.create(&friend).await.unwrap();
+        storage.create(&other_friend).await.unwrap();
+        assert_eq!(source.get_by_id(&friend.get_id()).await.unwrap().as_ref(), Some(&friend));
+        assert_eq!(storage.get_by_user_ids(&[friend.get_user_id(), other_friend.get_user_id()]).await.unwrap().len(), 2);
+        assert_eq!(storage.get_by_friend_ids(&[friend.get_user_id()]).await.unwrap(), vec![other_friend.clone()]);
+
+        storage.delete(&friend).await.unwrap();
+        assert_eq!(source.get_by_id(&friend.get_id()).await.unwrap(), None);
+        assert_eq!(target.get_by_id(&friend.get_id()).await.unwrap(), None);
+        assert_eq!(target.get_by_id(&other_friend.get_id()).await.unwrap(), Some(other_friend));
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+use futures::io;
+use tonic::async_trait;
+use uuid::Uuid;
+
+use crate::session::Session;
+use crate::session_storage::SessionStorage;
+
+// Writes go to both stores while data is moved between them. The source stays authoritative:
+// its errors are returned, target errors are only logged and fixed later by reconciliation
+pub struct DualWriteSessionStorage {
+    source: Arc<dyn SessionStorage + Send + Sync>,
+    target: Arc<dyn SessionStorage + Send + Sync>,
+    is_read_from_target: bool,
+}
+
+impl DualWriteSessionStorage {
+    pub fn new(
+        source: Arc<dyn SessionStorage + Send + Sync>,
+        target: Arc<dyn SessionStorage + Send + Sync>,
+        is_read_from_target: bool,
+    ) -> Self {
+        Self {
+            source,
+            target,
+            is_read_from_target,
+        }
+    }
+
+    fn get_read_storage(&self) -> &Arc<dyn SessionStorage + Send + Sync> {
+        if self.is_read_from_target { &self.target } else { &self.source }
+    }
+}
+
+fn log_target_error<T>(operation: &str, result: Result<T, io::Error>) {
+    if let Err(err) = result {
+        log::warn!("unable to {} session in migration target: {:?}", operation, err);
+    }
+}
+
+#[async_trait]
+impl SessionStorage for DualWriteSessionStorage {
+    async fn get_by_id(&self, id: &str) -> Result<Option<Session>, io::Error> {
+        self.get_read_storage().get_by_id(id).await
+    }
+
+    async fn get_by_user_id(&self, user_id: &Uuid) -> Result<Vec<Session>, io::Error> {
+        self.get_read_storage().get_by_user_id(user_id).await
+    }
+
+    async fn get_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Session>, io::Error> {
+        self.get_read_storage().get_batch(after_id, limit).await
+    }
+
+    async fn create(&self, session: &Session) -> Result<Uuid, io::Error> {
+        let id = self.source.create(session).await?;
+        log_target_error("create", self.target.create(session).await);
+
+        Ok(id)
+    }
+
+    async fn touch(&self, id: &Uuid, time_updated: &chrono::NaiveDateTime) -> Result<bool, io::Error> {
+        let is_touched = self.source.touch(id, time_updated).await?;
+        log_target_error("touch", self.target.touch(id, time_updated).await);
+
+        Ok(is_touched)
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<bool, io::Error> {
+        let is_deleted = self.source.delete(id).await?;
+        log_target_error("delete", self.target.delete(id).await);
+
+        Ok(is_deleted)
+    }
+
+    async fn delete_by_user_id(&self, user_id: &Uuid, except_id: Option<&Uuid>) -> Result<u64, io::Error> {
+        let deleted_count = self.source.delete_by_user_id(user_id, except_id).await?;
+        log_target_error("delete", self.target.delete_by_user_id(user_id, except_id).await);
+
+        Ok(deleted_count)
+    }
+}
@@ -0,0 +1,38 @@
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use futures::io;
+use tonic::async_trait;
+
+use crate::password_reset_sink::PasswordResetSink;
+use crate::user::User;
+
+pub struct FilePasswordResetSink {
+    path: String,
+}
+
+impl FilePasswordResetSink {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+        }
+    }
+}
+
+#[async_trait]
+impl PasswordResetSink for FilePasswordResetSink {
+    async fn deliver(&self, user: &User, token: &str) -> Result<(), io::Error> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        writeln!(
+            file,
+            "{}\t{}\t{}\t{}",
+            chrono::Utc::now().naive_utc(),
+            user.id(),
+            user.login().unwrap_or(""),
+            token,
+        )
+    }
+}
\ No newline at end of file
@@ -4,12 +4,18 @@ use futures::io;
 use serde::Deserialize;
 use serde::Serialize;
 use tokio::sync::OnceCell;
+use tokio_postgres::GenericClient;
 use uuid::Uuid;
 use deadpool_redis::Connection;
+use crate::friend_suggestion;
 use crate::post;
+use crate::user::User;
 
 use crate::friend_storage::FriendStorage;
 
+pub const LIST_DEFAULT_LIMIT: usize = 20;
+pub const LIST_MAX_LIMIT: usize = 100;
+
 static STORAGE: OnceCell<Box<dyn FriendStorage + Send + Sync>> = OnceCell::const_new();
 
 pub async fn init_storage(storage: Box<dyn FriendStorage + Send + Sync>) {
@@ -18,7 +24,7 @@ pub async fn init_storage(storage: Box<dyn FriendStorage + Send + Sync>) {
     }
 }
 
-fn get_storage() -> &'static Box<dyn FriendStorage + Send + Sync> {
+pub fn get_storage() -> &'static Box<dyn FriendStorage + Send + Sync> {
     STORAGE.get().expect("Storage must be initialized first")
 }
 
@@ -45,13 +51,67 @@ impl error::Error for FriendDataError {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Friend {
     id: Uuid,
     user_id: Uuid,
     friend_id: Uuid,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct FriendCounts {
+    followers_count: u64,
+    following_count: u64,
+}
+
+impl FriendCounts {
+    pub fn new(followers_count: u64, following_count: u64) -> FriendCounts {
+        FriendCounts {
+            followers_count,
+            following_count,
+        }
+    }
+
+    pub fn get_followers_count(&self) -> u64 {
+        self.followers_count
+    }
+
+    pub fn get_following_count(&self) -> u64 {
+        self.following_count
+    }
+}
+
+// Birthdate is only shown to mutual friends
+#[derive(Debug, Serialize)]
+pub struct FriendProfile {
+    id: Uuid,
+    first_name: String,
+    second_name: String,
+    city: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    birthdate: Option<chrono::NaiveDate>,
+    is_mutual: bool,
+}
+
+impl FriendProfile {
+    fn new(user: &User, is_mutual: bool) -> FriendProfile {
+        FriendProfile {
+            id: user.id(),
+            first_name: user.first_name().to_string(),
+            second_name: user.second_name().to_string(),
+            city: user.city().to_string(),
+            birthdate: if is_mutual { Some(*user.birthdate()) } else { None },
+            is_mutual,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FriendPage {
+    friends: Vec<FriendProfile>,
+    next_cursor: Option<Uuid>,
+}
+
 impl Friend {
     pub fn new(id: Option<Uuid>, user_id: Uuid, friend_id: Uuid) -> Friend {
         Friend {
@@ -85,19 +145,86 @@ impl Friend {
         get_storage().get_by_friend_id(friend_id).await
     }
 
-    pub async fn get_by_user_id_and_friend_id(user_id: &Uuid, friend_id: &Uuid) -> Result<Option<Friend>, io::Error> {
-        get_storage().get_by_user_id_and_friend_id(user_id, friend_id).await
+    pub async fn get_by_user_ids(user_ids: &[Uuid]) -> Result<Vec<Friend>, io::Error> {
+        get_storage().get_by_user_ids(user_ids).await
     }
 
-    pub async fn create(friend: &Friend, redis_connection: &mut Connection) -> Result<Uuid, io::Error> {
-        post::Post::cache_invalidate_by_friend_user_id(redis_connection, &friend.user_id).await.unwrap();
+    pub async fn get_by_friend_ids(friend_ids: &[Uuid]) -> Result<Vec<Friend>, io::Error> {
+        get_storage().get_by_friend_ids(friend_ids).await
+    }
+
+    // Users that both users have added as friends
+    pub async fn get_mutual_friend_ids(user_id: &Uuid, other_user_id: &Uuid) -> Result<Vec<Uuid>, io::Error> {
+        let other_friend_ids: Vec<Uuid> = get_storage().get_by_user_id(other_user_id).await?
+            .iter()
+            .map(|friend| friend.get_friend_id())
+            .collect();
+
+        Ok(get_storage().get_by_user_id(user_id).await?
+            .iter()
+            .map(|friend| friend.get_friend_id())
+            .filter(|friend_id| other_friend_ids.contains(friend_id))
+            .collect())
+    }
+
+    // Profiles of one page are resolved with a single users query, the cursor is the id of the last friend returned
+    pub async fn get_page<C: GenericClient>(
+        client: &C,
+        user_id: &Uuid,
+        name_prefix: &str,
+        cursor: Option<&Uuid>,
+        limit: usize,
+    ) -> Result<FriendPage, io::Error> {
+        let limit = match limit {
+            0 => LIST_DEFAULT_LIMIT,
+            value => value.min(LIST_MAX_LIMIT),
+        };
+
+        let friend_ids: Vec<Uuid> = get_storage().get_by_user_id(user_id).await?
+            .iter()
+            .map(|friend| friend.get_friend_id())
+            .collect();
+        if friend_ids.is_empty() {
+            return Ok(FriendPage { friends: Vec::new(), next_cursor: None });
+        }
+
+        let mut users = User::get_page_by_ids(client, &friend_ids, name_prefix, cursor, limit + 1).await
+            .map_err(io::Error::other)?;
+        let has_next_page = limit < users.len();
+        users.truncate(limit);
+
+        let follower_ids: Vec<Uuid> = get_storage().get_by_friend_id(user_id).await?
+            .iter()
+            .map(|friend| friend.get_user_id())
+            .collect();
+
+        Ok(FriendPage {
+            next_cursor: if has_next_page { users.last().map(|user| user.id()) } else { None },
+            friends: users.iter()
+                .map(|user| FriendProfile::new(user, follower_ids.contains(&user.id())))
+                .collect(),
+        })
+    }
 
-        get_storage().create(friend).await
+    pub async fn get_counts(user_id: &Uuid) -> Result<FriendCounts, io::Error> {
+        get_storage().get_counts(user_id).await
+    }
+
+    pub async fn get_by_user_id_and_friend_id(user_id: &Uuid, friend_id: &Uuid) -> Result<Option<Friend>, io::Error> {
+        get_storage().get_by_user_id_and_friend_id(user_id, friend_id).await
     }
 
     pub async fn delete(friend: &Friend, redis_connection: &mut Connection) -> Result<bool, io::Error> {
         post::Post::cache_invalidate_by_friend_user_id(redis_connection, &friend.user_id).await.unwrap();
+        let is_deleted = get_storage().delete(friend).await?;
+        // Suggestions are ranked from the stored friends, so they are purged only once the record is gone
+        friend_suggestion::invalidate(redis_connection, &[friend.user_id, friend.friend_id]).await?;
+
+        Ok(is_deleted)
+    }
 
+    // Leaves feed caches and suggestions as they are, callers purge them on their own
+    pub async fn delete_relation(friend: &Friend) -> Result<bool, io::Error> {
         get_storage().delete(friend).await
     }
 
@@ -0,0 +1,145 @@
+use std::fmt;
+use std::str::FromStr;
+use futures::io;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::friend::{self, Friend};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FriendRequestStatus {
+    Pending,
+    Accepted,
+    Declined,
+    Cancelled,
+}
+
+impl fmt::Display for FriendRequestStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FriendRequestStatus::Pending => write!(f, "pending"),
+            FriendRequestStatus::Accepted => write!(f, "accepted"),
+            FriendRequestStatus::Declined => write!(f, "declined"),
+            FriendRequestStatus::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl FromStr for FriendRequestStatus {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "pending" => Ok(FriendRequestStatus::Pending),
+            "accepted" => Ok(FriendRequestStatus::Accepted),
+            "declined" => Ok(FriendRequestStatus::Declined),
+            "cancelled" => Ok(FriendRequestStatus::Cancelled),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unknown friend request status: ".to_owned() + value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FriendRequest {
+    id: Uuid,
+    from_user_id: Uuid,
+    to_user_id: Uuid,
+    status: FriendRequestStatus,
+    time_created: chrono::NaiveDateTime,
+    time_updated: chrono::NaiveDateTime,
+}
+
+impl FriendRequest {
+    pub fn new(from_user_id: Uuid, to_user_id: Uuid) -> FriendRequest {
+        let now = chrono::Utc::now().naive_utc();
+        FriendRequest::restore(Uuid::new_v4(), from_user_id, to_user_id, FriendRequestStatus::Pending, now, now)
+    }
+
+    pub fn restore(
+        id: Uuid,
+        from_user_id: Uuid,
+        to_user_id: Uuid,
+        status: FriendRequestStatus,
+        time_created: chrono::NaiveDateTime,
+        time_updated: chrono::NaiveDateTime,
+    ) -> FriendRequest {
+        FriendRequest {
+            id,
+            from_user_id,
+            to_user_id,
+            status,
+            time_created,
+            time_updated,
+        }
+    }
+
+    pub fn get_id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn get_from_user_id(&self) -> Uuid {
+        self.from_user_id
+    }
+
+    pub fn get_to_user_id(&self) -> Uuid {
+        self.to_user_id
+    }
+
+    pub fn get_status(&self) -> FriendRequestStatus {
+        self.status
+    }
+
+    pub fn get_time_created(&self) -> &chrono::NaiveDateTime {
+        &self.time_created
+    }
+
+    pub fn get_time_updated(&self) -> &chrono::NaiveDateTime {
+        &self.time_updated
+    }
+
+    pub fn set_status(&mut self, status: FriendRequestStatus) {
+        self.status = status;
+        self.time_updated = chrono::Utc::now().naive_utc();
+    }
+
+    // The relationship is symmetric once accepted
+    pub fn to_friends(&self) -> [Friend; 2] {
+        [
+            Friend::new(None, self.from_user_id, self.to_user_id),
+            Friend::new(None, self.to_user_id, self.from_user_id),
+        ]
+    }
+
+    pub async fn get_by_id(id: &Uuid) -> Result<Option<FriendRequest>, io::Error> {
+        friend::get_storage().get_request_by_id(id).await
+    }
+
+    pub async fn get_pending(from_user_id: &Uuid, to_user_id: &Uuid) -> Result<Option<FriendRequest>, io::Error> {
+        friend::get_storage().get_pending_request(from_user_id, to_user_id).await
+    }
+
+    pub async fn get_incoming(user_id: &Uuid) -> Result<Vec<FriendRequest>, io::Error> {
+        friend::get_storage().get_pending_requests_by_to_user_id(user_id).await
+    }
+
+    pub async fn get_outgoing(user_id: &Uuid) -> Result<Vec<FriendRequest>, io::Error> {
+        friend::get_storage().get_pending_requests_by_from_user_id(user_id).await
+    }
+
+    pub async fn create(request: &FriendRequest) -> Result<Uuid, io::Error> {
+        friend::get_storage().create_request(request).await
+    }
+
+    pub async fn delete_by_user_id(user_id: &Uuid) -> Result<u64, io::Error> {
+        friend::get_storage().delete_requests_by_user_id(user_id).await
+    }
+
+    // Returns false when the request is no longer pending
+    pub async fn resolve(request: &FriendRequest) -> Result<bool, io::Error> {
+        match request.status {
+            FriendRequestStatus::Accepted => friend::get_storage().accept_request(request, &request.to_friends()).await,
+            _ => friend::get_storage().update_request_status(request).await,
+        }
+    }
+}
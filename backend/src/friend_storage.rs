@@ -2,15 +2,42 @@ use std::io::Error;
 use tonic::async_trait;
 use uuid::Uuid;
 
-use crate::friend::Friend;
+use crate::friend::{Friend, FriendCounts};
+use crate::friend_request::FriendRequest;
+use crate::user_block::UserBlock;
 
 #[async_trait]
 pub trait FriendStorage {
     async fn get_by_id(&self, id: &Uuid) -> Result<Option<Friend>, Error>;
     async fn get_by_user_id(&self, user_id: &Uuid) -> Result<Vec<Friend>, Error>;
     async fn get_by_friend_id(&self, friend_id: &Uuid) -> Result<Vec<Friend>, Error>;
+    // Same as get_by_user_id and get_by_friend_id for many users in one round trip
+    async fn get_by_user_ids(&self, user_ids: &[Uuid]) -> Result<Vec<Friend>, Error>;
+    async fn get_by_friend_ids(&self, friend_ids: &[Uuid]) -> Result<Vec<Friend>, Error>;
     async fn get_by_user_id_and_friend_id(&self, user_id: &Uuid, friend_id: &Uuid) -> Result<Option<Friend>, Error>;
+    async fn get_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Friend>, Error>;
     async fn create(&self, friend: &Friend) -> Result<Uuid, Error>;
     async fn delete(&self, friend: &Friend) -> Result<bool, Error>;
     async fn is_persistant(&self, friend: &Friend) -> Result<bool, Error>;
+    // Counters are maintained on every create, delete and accepted request
+    async fn get_counts(&self, user_id: &Uuid) -> Result<FriendCounts, Error>;
+
+    async fn get_request_by_id(&self, id: &Uuid) -> Result<Option<FriendRequest>, Error>;
+    async fn get_pending_request(&self, from_user_id: &Uuid, to_user_id: &Uuid) -> Result<Option<FriendRequest>, Error>;
+    async fn get_pending_requests_by_to_user_id(&self, to_user_id: &Uuid) -> Result<Vec<FriendRequest>, Error>;
+    async fn get_pending_requests_by_from_user_id(&self, from_user_id: &Uuid) -> Result<Vec<FriendRequest>, Error>;
+    async fn create_request(&self, request: &FriendRequest) -> Result<Uuid, Error>;
+    // Moves a pending request to the status of the given one, false when it is not pending anymore
+    async fn update_request_status(&self, request: &FriendRequest) -> Result<bool, Error>;
+    // Same as update_request_status, also creates the missing friend records in one go
+    async fn accept_request(&self, request: &FriendRequest, friends: &[Friend]) -> Result<bool, Error>;
+    async fn delete_requests_by_user_id(&self, user_id: &Uuid) -> Result<u64, Error>;
+
+    async fn get_block(&self, user_id: &Uuid, blocked_user_id: &Uuid) -> Result<Option<UserBlock>, Error>;
+    async fn get_blocks_by_user_id(&self, user_id: &Uuid) -> Result<Vec<UserBlock>, Error>;
+    async fn get_blocks_by_blocked_user_id(&self, blocked_user_id: &Uuid) -> Result<Vec<UserBlock>, Error>;
+    // Blocking the same user twice keeps the first block
+    async fn create_block(&self, block: &UserBlock) -> Result<Uuid, Error>;
+    async fn delete_block(&self, user_id: &Uuid, blocked_user_id: &Uuid) -> Result<bool, Error>;
+    async fn delete_blocks_by_user_id(&self, user_id: &Uuid) -> Result<u64, Error>;
 }
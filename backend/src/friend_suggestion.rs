@@ -0,0 +1,140 @@
+use std::collections::{HashMap, HashSet};
+use deadpool_redis::Connection;
+use futures::io;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::GenericClient;
+use uuid::Uuid;
+
+use crate::friend::Friend;
+use crate::redis;
+use crate::user::User;
+use crate::user_block::UserBlock;
+
+pub const SUGGESTIONS_CACHE_KEY_PREFIX: &str = "friend_suggestions:";
+
+lazy_static! {
+    pub static ref FRIEND_SUGGESTIONS_LIMIT: usize = std::env::var("FRIEND_SUGGESTIONS_LIMIT").unwrap_or_else(|_| "20".to_string()).parse::<usize>().unwrap_or(20);
+    pub static ref FRIEND_SUGGESTIONS_CACHE_TTL_SECONDS: u64 = std::env::var("FRIEND_SUGGESTIONS_CACHE_TTL_SECONDS").unwrap_or_else(|_| "3600".to_string()).parse::<u64>().unwrap_or(3600);
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RankedCandidate {
+    user_id: Uuid,
+    mutual_friends_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FriendSuggestion {
+    user: User,
+    mutual_friends_count: usize,
+}
+
+fn get_cache_key(user_id: &Uuid) -> String {
+    SUGGESTIONS_CACHE_KEY_PREFIX.to_string() + user_id.to_string().as_str()
+}
+
+// Only the ranking is cached, profiles are loaded on every request so that deleted users drop out
+pub async fn get<C: GenericClient>(
+    client: &C,
+    redis_connection: &mut Connection,
+    user_id: &Uuid,
+) -> Result<Vec<FriendSuggestion>, io::Error> {
+    let cache_key = get_cache_key(user_id);
+    let cached = match redis::get_optional(&cache_key, redis_connection).await {
+        Ok(cached) => cached.and_then(|value| serde_json::from_str::<Vec<RankedCandidate>>(&value).ok()),
+        Err(err) => {
+            log::warn!("unable to read friend suggestions cache '{}': {:?}", cache_key, err);
+            None
+        }
+    };
+
+    let candidates = match cached {
+        Some(candidates) => candidates,
+        None => {
+            let candidates = rank(client, user_id).await?;
+            if let Err(err) = redis::set_ex(&cache_key, &serde_json::to_string(&candidates)?, &FRIEND_SUGGESTIONS_CACHE_TTL_SECONDS, redis_connection).await {
+                log::warn!("unable to write friend suggestions cache '{}': {:?}", cache_key, err);
+            }
+            candidates
+        }
+    };
+
+    let ids: Vec<Uuid> = candidates.iter().map(|candidate| candidate.user_id).collect();
+    let mut users: HashMap<Uuid, User> = User::get_by_ids(client, &ids).await
+        .map_err(io::Error::other)?
+        .into_iter()
+        .map(|user| (user.id(), user))
+        .collect();
+
+    Ok(candidates.into_iter()
+        .filter_map(|candidate| users.remove(&candidate.user_id).map(|user| FriendSuggestion {
+            user,
+            mutual_friends_count: candidate.mutual_friends_count,
+        }))
+        .collect())
+}
+
+// Friends of friends ordered by the number of mutual friends, then by living in the same city.
+// Blocked users in either direction are never suggested
+async fn rank<C: GenericClient>(client: &C, user_id: &Uuid) -> Result<Vec<RankedCandidate>, io::Error> {
+    let friend_ids: HashSet<Uuid> = Friend::get_by_user_id(user_id).await?
+        .iter()
+        .map(|friend| friend.get_friend_id())
+        .collect();
+
+    let friends_of_friends = if friend_ids.is_empty() {
+        Vec::new()
+    } else {
+        Friend::get_by_user_ids(&friend_ids.iter().copied().collect::<Vec<Uuid>>()).await?
+    };
+
+    let hidden_user_ids = UserBlock::get_hidden_user_ids(user_id).await?;
+
+    let mut mutual_friends_counts: HashMap<Uuid, usize> = HashMap::new();
+    for friend in friends_of_friends.iter() {
+        let candidate_id = friend.get_friend_id();
+        if candidate_id == *user_id || friend_ids.contains(&candidate_id) || hidden_user_ids.contains(&candidate_id) {
+            continue;
+        }
+        *mutual_friends_counts.entry(candidate_id).or_insert(0) += 1;
+    }
+    if mutual_friends_counts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut ids: Vec<Uuid> = mutual_friends_counts.keys().copied().collect();
+    ids.push(*user_id);
+    let cities: HashMap<Uuid, String> = User::get_by_ids(client, &ids).await
+        .map_err(io::Error::other)?
+        .into_iter()
+        .map(|user| (user.id(), user.city().to_string()))
+        .collect();
+    let city = cities.get(user_id);
+
+    let mut candidates: Vec<(RankedCandidate, bool)> = mutual_friends_counts.into_iter()
+        .filter(|(candidate_id, _)| cities.contains_key(candidate_id))
+        .map(|(candidate_id, mutual_friends_count)| {
+            let is_same_city = city.is_some_and(|city| !city.is_empty() && cities.get(&candidate_id) == Some(city));
+            (RankedCandidate { user_id: candidate_id, mutual_friends_count }, is_same_city)
+        })
+        .collect();
+    candidates.sort_by(|(left, left_same_city), (right, right_same_city)| {
+        right.mutual_friends_count.cmp(&left.mutual_friends_count)
+            .then(right_same_city.cmp(left_same_city))
+            .then(left.user_id.cmp(&right.user_id))
+    });
+    candidates.truncate(*FRIEND_SUGGESTIONS_LIMIT);
+
+    Ok(candidates.into_iter().map(|(candidate, _)| candidate).collect())
+}
+
+// A change between two users affects their own suggestions and the mutual friend counts seen by their friends
+pub async fn invalidate(redis_connection: &mut Connection, user_ids: &[Uuid]) -> Result<(), io::Error> {
+    let mut affected_ids: HashSet<Uuid> = user_ids.iter().copied().collect();
+    affected_ids.extend(Friend::get_by_user_ids(user_ids).await?.iter().map(|friend| friend.get_friend_id()));
+    affected_ids.extend(Friend::get_by_friend_ids(user_ids).await?.iter().map(|friend| friend.get_user_id()));
+
+    let cache_keys: Vec<String> = affected_ids.iter().map(get_cache_key).collect();
+    redis::del_all(&cache_keys, redis_connection).await.map_err(io::Error::other)
+}
@@ -0,0 +1,21 @@
+use futures::io;
+use tonic::async_trait;
+
+use crate::password_reset_sink::PasswordResetSink;
+use crate::user::User;
+
+pub struct LogPasswordResetSink {}
+
+impl LogPasswordResetSink {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[async_trait]
+impl PasswordResetSink for LogPasswordResetSink {
+    async fn deliver(&self, user: &User, token: &str) -> Result<(), io::Error> {
+        log::info!("Password reset token for user '{}': {}", user.id(), token);
+        Ok(())
+    }
+}
\ No newline at end of file
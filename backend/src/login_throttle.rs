@@ -0,0 +1,111 @@
+use std::cmp;
+use deadpool_redis::Connection;
+use lazy_static::lazy_static;
+use uuid::Uuid;
+
+use crate::redis;
+
+pub const LOGIN_FAILURES_KEY_PREFIX: &str = "login:failures:";
+pub const LOGIN_LOCKOUT_KEY_PREFIX: &str = "login:lockout:";
+
+lazy_static! {
+    pub static ref LOGIN_MAX_FAILURES_PER_ACCOUNT: u64 = std::env::var("LOGIN_MAX_FAILURES_PER_ACCOUNT").unwrap_or_else(|_| "5".to_string()).parse::<u64>().unwrap_or(5);
+    pub static ref LOGIN_MAX_FAILURES_PER_IP: u64 = std::env::var("LOGIN_MAX_FAILURES_PER_IP").unwrap_or_else(|_| "20".to_string()).parse::<u64>().unwrap_or(20);
+    pub static ref LOGIN_FAILURES_WINDOW_SECONDS: u64 = std::env::var("LOGIN_FAILURES_WINDOW_SECONDS").unwrap_or_else(|_| "900".to_string()).parse::<u64>().unwrap_or(900);
+    pub static ref LOGIN_LOCKOUT_BASE_SECONDS: u64 = std::env::var("LOGIN_LOCKOUT_BASE_SECONDS").unwrap_or_else(|_| "30".to_string()).parse::<u64>().unwrap_or(30);
+    pub static ref LOGIN_LOCKOUT_MAX_SECONDS: u64 = std::env::var("LOGIN_LOCKOUT_MAX_SECONDS").unwrap_or_else(|_| "3600".to_string()).parse::<u64>().unwrap_or(3600);
+    pub static ref LOGIN_TRUST_PROXY_HEADERS: bool = std::env::var("LOGIN_TRUST_PROXY_HEADERS").unwrap_or_else(|_| "false".to_string()).as_str() == "true";
+}
+
+// Every failure past the threshold doubles the lockout, up to the configured maximum
+fn get_lockout_seconds_for(failures: u64, max_failures: u64, base_seconds: u64, max_seconds: u64) -> Option<u64> {
+    if failures < max_failures {
+        return None;
+    }
+
+    let exponent = cmp::min(failures - max_failures, 32) as u32;
+    Some(cmp::min(base_seconds.saturating_mul(2_u64.saturating_pow(exponent)), max_seconds))
+}
+
+pub struct LoginThrottle {
+    account_key: String,
+    ip_key: String,
+}
+
+impl LoginThrottle {
+    // Failures are counted per user whether the id or the login was used, identifiers that don't
+    // belong to any user get their own counters
+    pub fn new(user_id: Option<&Uuid>, identifier: &str, ip: &str) -> LoginThrottle {
+        LoginThrottle {
+            account_key: match user_id {
+                Some(user_id) => "account:".to_owned() + user_id.to_string().as_str(),
+                None => "unknown:".to_owned() + identifier.trim().to_lowercase().as_str(),
+            },
+            ip_key: "ip:".to_owned() + ip,
+        }
+    }
+
+    // Returns the number of seconds left until the lockout ends, if any
+    pub async fn get_lockout_seconds(&self, conn: &mut Connection) -> Result<Option<u64>, deadpool_redis::redis::RedisError> {
+        let account_ttl = redis::ttl((LOGIN_LOCKOUT_KEY_PREFIX.to_owned() + &self.account_key).as_str(), conn).await?;
+        let ip_ttl = redis::ttl((LOGIN_LOCKOUT_KEY_PREFIX.to_owned() + &self.ip_key).as_str(), conn).await?;
+        let ttl = cmp::max(account_ttl, ip_ttl);
+
+        Ok(if 0 < ttl { Some(ttl as u64) } else { None })
+    }
+
+    pub async fn register_failure(&self, conn: &mut Connection) -> Result<(), deadpool_redis::redis::RedisError> {
+        self.register_failure_by_key(&self.account_key, &LOGIN_MAX_FAILURES_PER_ACCOUNT, conn).await?;
+        self.register_failure_by_key(&self.ip_key, &LOGIN_MAX_FAILURES_PER_IP, conn).await
+    }
+
+    pub async fn register_success(&self, conn: &mut Connection) -> Result<(), deadpool_redis::redis::RedisError> {
+        redis::del((LOGIN_FAILURES_KEY_PREFIX.to_owned() + &self.account_key).as_str(), conn).await?;
+        redis::del((LOGIN_LOCKOUT_KEY_PREFIX.to_owned() + &self.account_key).as_str(), conn).await
+    }
+
+    async fn register_failure_by_key(&self, key: &str, max_failures: &u64, conn: &mut Connection) -> Result<(), deadpool_redis::redis::RedisError> {
+        let failures_key = LOGIN_FAILURES_KEY_PREFIX.to_owned() + key;
+        let failures = redis::incr(failures_key.as_str(), conn).await?;
+        if 1 == failures {
+            redis::expire(failures_key.as_str(), &LOGIN_FAILURES_WINDOW_SECONDS, conn).await?;
+        }
+
+        let lockout_seconds = match get_lockout_seconds_for(failures, *max_failures, *LOGIN_LOCKOUT_BASE_SECONDS, *LOGIN_LOCKOUT_MAX_SECONDS) {
+            Some(lockout_seconds) => lockout_seconds,
+            None => return Ok(()),
+        };
+        log::debug!("Locking out login attempts for '{}' for {} seconds", key, lockout_seconds);
+
+        redis::set_ex(
+            (LOGIN_LOCKOUT_KEY_PREFIX.to_owned() + key).as_str(),
+            failures.to_string().as_str(),
+            &lockout_seconds,
+            conn
+        ).await?;
+        redis::expire(failures_key.as_str(), &cmp::max(*LOGIN_FAILURES_WINDOW_SECONDS, lockout_seconds), conn).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lockout_starts_at_threshold() {
+        assert_eq!(get_lockout_seconds_for(4, 5, 30, 3600), None);
+        assert_eq!(get_lockout_seconds_for(5, 5, 30, 3600), Some(30));
+    }
+
+    #[test]
+    fn lockout_doubles_with_every_failure() {
+        assert_eq!(get_lockout_seconds_for(6, 5, 30, 3600), Some(60));
+        assert_eq!(get_lockout_seconds_for(8, 5, 30, 3600), Some(240));
+    }
+
+    #[test]
+    fn lockout_is_capped() {
+        assert_eq!(get_lockout_seconds_for(12, 5, 30, 3600), Some(3600));
+        assert_eq!(get_lockout_seconds_for(u64::MAX, 5, u64::MAX, 3600), Some(3600));
+    }
+}
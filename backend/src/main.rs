@@ -14,47 +14,114 @@ use actix_web::{
 };
 use deadpool_postgres::Pool as PostgresPool;
 use deadpool_redis::Pool as RedisPool;
+use file_password_reset_sink::FilePasswordResetSink;
 use futures::{future, stream, StreamExt};
+use log_password_reset_sink::LogPasswordResetSink;
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
-use postgres_friend_storage::PostgresFriendStorage;
 use prost::Message;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tarantool::{TarantoolClientConfig, TarantoolClientManager};
-use tarantool_friend_storage::TarantoolFriendStorage;
-use tarantool_session_storage::TarantoolSessionStorage;
 use tonic::IntoRequest;
-use user_search::user_search_server::{
-    UserSearch,
-    // UserSearchServer
+use user_search::user_search_server::{UserSearch, UserSearchServer};
+use user_search::user_service_server::{UserService, UserServiceServer};
+use user_search::{
+    BatchGetUsersRequest,
+    BatchGetUsersResponse,
+    GetFeedRequest,
+    GetFeedResponse,
+    GetFriendsRequest,
+    GetFriendsResponse,
+    GetUserRequest,
+    GetUserResponse,
+    UserSearchRequest,
+    UserSearchResponse,
 };
-use user_search::{UserSearchRequest, UserSearchResponse};
-// use tonic::codec::CompressionEncoding;
-use actix_web_httpauth::extractors::bearer::{self, BearerAuth};
+use tonic::codec::CompressionEncoding;
+use actix_web_httpauth::extractors::bearer;
 // use amqprs::channel::Channel;
 use reqwest;
 use uuid::Uuid;
 
+mod access_token;
+mod account_deletion;
+mod auth;
+mod dual_write_friend_storage;
+mod dual_write_session_storage;
+mod file_password_reset_sink;
 mod friend;
+mod friend_request;
+mod friend_suggestion;
 mod friend_storage;
+mod log_password_reset_sink;
+mod login_throttle;
+mod memory_friend_storage;
+mod memory_session_storage;
+mod password_reset;
+mod password_reset_sink;
 mod post;
 mod postgres;
 mod postgres_friend_storage;
 mod postgres_session_storage;
 mod rabbitmq;
 mod redis;
+mod redis_session_storage;
 mod session;
 mod session_storage;
+mod storage;
+mod storage_migration;
 mod tarantool;
 mod tarantool_friend_storage;
 mod tarantool_session_storage;
 mod user;
+mod user_block;
+mod user_export;
 mod user_search;
 mod websocket;
 
 const MAX_SIZE: usize = 262_144; // max payload size is 256k
 
-async fn list_users(pool: web::Data<&'static PostgresPool>) -> HttpResponse {
+const USER_LIST_DEFAULT_LIMIT: usize = 50;
+const USER_LIST_MAX_LIMIT: usize = 500;
+const USER_BATCH_MAX_IDS: usize = 500;
+
+#[derive(Deserialize)]
+struct UserListRequestQuery {
+    cursor: Option<String>,
+    limit: Option<usize>,
+    city: Option<String>,
+    birthdate_from: Option<String>,
+    birthdate_to: Option<String>,
+}
+
+#[derive(Serialize)]
+struct UserListResponse {
+    users: Vec<user::User>,
+    next_cursor: Option<String>,
+}
+
+fn parse_birthdate_filter(value: &Option<String>) -> Result<Option<chrono::NaiveDate>, chrono::ParseError> {
+    value.as_ref().map(|value| chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")).transpose()
+}
+
+async fn list_users(
+    pool: web::Data<&'static PostgresPool>,
+    query: web::Query<UserListRequestQuery>,
+) -> HttpResponse {
+    // The cursor is the id of the last user of the previous page
+    let after_id = match query.cursor.as_ref().map(|cursor| Uuid::parse_str(cursor)).transpose() {
+        Ok(after_id) => after_id,
+        Err(err) => {
+            log::debug!("unable to parse cursor: {:?}", err);
+            return HttpResponse::BadRequest().json("cursor is malformed");
+        }
+    };
+    let (birthdate_from, birthdate_to) = match (parse_birthdate_filter(&query.birthdate_from), parse_birthdate_filter(&query.birthdate_to)) {
+        (Ok(birthdate_from), Ok(birthdate_to)) => (birthdate_from, birthdate_to),
+        _ => return HttpResponse::BadRequest().json("birthdate format is incorrect, should be %Y-%m-%d"),
+    };
+    let limit = query.limit.unwrap_or(USER_LIST_DEFAULT_LIMIT).clamp(1, USER_LIST_MAX_LIMIT);
+
     let client = match pool.get().await {
         Ok(client) => client,
         Err(err) => {
@@ -62,8 +129,26 @@ async fn list_users(pool: web::Data<&'static PostgresPool>) -> HttpResponse {
             return HttpResponse::InternalServerError().json("unable to get postgres client");
         }
     };
-    match user::User::get_all(&**client).await {
-        Ok(list) => HttpResponse::Ok().json(list),
+    // One extra row tells whether there is a next page
+    match user::User::get_page(
+        &**client,
+        after_id.as_ref(),
+        query.city.as_deref(),
+        birthdate_from.as_ref(),
+        birthdate_to.as_ref(),
+        limit + 1,
+    )
+    .await
+    {
+        Ok(mut users) => {
+            let next_cursor = if limit < users.len() {
+                users.truncate(limit);
+                users.last().map(|user| user.id().to_string())
+            } else {
+                None
+            };
+            HttpResponse::Ok().json(UserListResponse { users, next_cursor })
+        }
         Err(err) => {
             log::debug!("unable to fetch users: {:?}", err);
             return HttpResponse::InternalServerError().json("unable to fetch users");
@@ -71,6 +156,14 @@ async fn list_users(pool: web::Data<&'static PostgresPool>) -> HttpResponse {
     }
 }
 
+#[derive(Serialize)]
+struct UserProfileResponse {
+    #[serde(flatten)]
+    user: user::User,
+    #[serde(flatten)]
+    counts: friend::FriendCounts,
+}
+
 async fn get_user(pool: web::Data<&'static PostgresPool>, path: web::Path<String>) -> HttpResponse {
     let id = path.parse::<String>().unwrap();
     let client = match pool.get().await {
@@ -80,25 +173,117 @@ async fn get_user(pool: web::Data<&'static PostgresPool>, path: web::Path<String
             return HttpResponse::InternalServerError().json("unable to get postgres client");
         }
     };
-    match user::User::get_by_id(&**client, &id).await {
-        Ok(user) => HttpResponse::Ok().json(user),
+    let user = match user::User::get_by_id(&**client, &id).await {
+        Ok(user) => user,
         Err(err) => {
             log::debug!("unable to fetch users: {:?}", err);
             return HttpResponse::InternalServerError().json("unable to fetch users");
         }
+    };
+    match friend::Friend::get_counts(&user.id()).await {
+        Ok(counts) => HttpResponse::Ok().json(UserProfileResponse { user, counts }),
+        Err(err) => {
+            log::debug!("unable to fetch friend counts: {:?}", err);
+            HttpResponse::InternalServerError().json("unable to fetch friend counts")
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct UserBatchResponse {
+    users: Vec<user::User>,
+    not_found_ids: Vec<Uuid>,
+}
+
+// Requested ids missing from the result, in request order
+fn get_not_found_ids(ids: &[Uuid], users: &[user::User]) -> Vec<Uuid> {
+    ids.iter()
+        .filter(|id| !users.iter().any(|user| user.id() == **id))
+        .cloned()
+        .collect()
+}
+
+async fn get_users_batch(
+    pool: web::Data<&'static PostgresPool>,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, Error> {
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if (body.len() + chunk.len()) > MAX_SIZE {
+            return Err(error::ErrorBadRequest("overflow"));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct UserBatchPayload {
+        ids: Vec<String>,
+    }
+
+    let user_batch_data = match serde_json::from_slice::<UserBatchPayload>(&body) {
+        Ok(user_batch_data) => user_batch_data,
+        Err(err) => {
+            log::debug!("unable to parse json data: {:?}", err);
+            return Ok(HttpResponse::BadRequest().json("unable to parse json data"));
+        }
+    };
+
+    let mut ids = match user_batch_data.ids.iter().map(|id| Uuid::parse_str(id)).collect::<Result<Vec<Uuid>, _>>() {
+        Ok(ids) => ids,
+        Err(err) => {
+            log::debug!("unable to parse user id: {:?}", err);
+            return Ok(HttpResponse::BadRequest().json("ids contain a malformed user id"));
+        }
+    };
+    let mut seen_ids = std::collections::HashSet::new();
+    ids.retain(|id| seen_ids.insert(*id));
+    if USER_BATCH_MAX_IDS < ids.len() {
+        return Ok(HttpResponse::BadRequest().json(format!("no more than {} ids are allowed", USER_BATCH_MAX_IDS)));
+    }
+
+    let client = match pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get postgres client: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get postgres client"));
+        }
+    };
+    match user::User::get_by_ids(&**client, &ids).await {
+        Ok(users) => {
+            let not_found_ids = get_not_found_ids(&ids, &users);
+            Ok(HttpResponse::Ok().json(UserBatchResponse { users, not_found_ids }))
+        }
+        Err(err) => {
+            log::debug!("unable to fetch users: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to fetch users"))
+        }
     }
 }
 
 #[derive(Deserialize)]
 struct UserSearchRequestQuery {
+    #[serde(default)]
     first_name: String,
+    #[serde(default)]
     last_name: String,
+    mode: Option<String>,
+    page_number: Option<usize>,
+    results_per_page: Option<usize>,
 }
 
 async fn search_user(
     pool: web::Data<&'static PostgresPool>,
     search: web::Query<UserSearchRequestQuery>,
 ) -> HttpResponse {
+    let mode = match user::UserSearchMode::from_str(search.mode.as_deref().unwrap_or_default()) {
+        Ok(mode) => mode,
+        Err(err) => return HttpResponse::BadRequest().json(err.to_string()),
+    };
+    if search.first_name.trim().is_empty() && search.last_name.trim().is_empty() {
+        return HttpResponse::BadRequest().json("first_name or last_name is required");
+    }
+
     let client = match pool.get().await {
         Ok(client) => client,
         Err(err) => {
@@ -106,16 +291,17 @@ async fn search_user(
             return HttpResponse::InternalServerError().json("unable to get postgres client");
         }
     };
-    let use_fast_search = pool.status().available < 0;
-    match user::User::search_by_first_name_and_last_name(
+    match user::User::search_page(
         &**client,
         &search.first_name,
         &search.last_name,
-        use_fast_search,
+        mode,
+        search.page_number.unwrap_or(1),
+        search.results_per_page.unwrap_or_default(),
     )
     .await
     {
-        Ok(users) => HttpResponse::Ok().json(users),
+        Ok(page) => HttpResponse::Ok().json(page),
         Err(err) => {
             log::debug!("unable to find users: {:?}", err);
             return HttpResponse::InternalServerError().json("unable to find users");
@@ -143,6 +329,7 @@ async fn register_user(
         birthdate: String,
         biography: String,
         city: String,
+        login: Option<String>,
         password: String,
     }
 
@@ -177,6 +364,7 @@ async fn register_user(
         &user_data.birthdate,
         &user_data.biography,
         &user_data.city,
+        &user_data.login,
     ) {
         Ok(user) => user,
         Err(e) => {
@@ -196,6 +384,10 @@ async fn register_user(
                 user_id: token.to_string(),
             }))
         }
+        Err(user::UserCreateError::LoginTaken) => {
+            log::debug!("unable to register user: login is already taken");
+            return Ok(HttpResponse::Conflict().json("unable to register user: login is already taken"));
+        }
         Err(err) => {
             log::debug!("unable to register user: {:?}", err);
             return Ok(HttpResponse::InternalServerError().json("unable to register user"));
@@ -204,7 +396,9 @@ async fn register_user(
 }
 
 async fn login(
+    req: HttpRequest,
     pool: web::Data<&'static PostgresPool>,
+    redis_pool: web::Data<&'static RedisPool>,
     mut payload: web::Payload,
 ) -> Result<HttpResponse, Error> {
     let mut body = web::BytesMut::new();
@@ -218,7 +412,8 @@ async fn login(
 
     #[derive(Debug, Serialize, Deserialize)]
     struct LoginPayload {
-        id: String,
+        id: Option<String>,
+        login: Option<String>,
         password: String,
     }
 
@@ -230,6 +425,21 @@ async fn login(
         }
     };
 
+    let account = match (&login_data.id, &login_data.login) {
+        (Some(id), _) => id.to_string(),
+        (None, Some(login)) => login.to_string(),
+        (None, None) => {
+            log::debug!("unable to authenticate user: neither id nor login is specified");
+            return Ok(HttpResponse::BadRequest().json("either id or login should be specified"));
+        }
+    };
+
+    let client_ip = if *login_throttle::LOGIN_TRUST_PROXY_HEADERS {
+        req.connection_info().realip_remote_addr().unwrap_or("unknown").to_string()
+    } else {
+        req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_else(|| String::from("unknown"))
+    };
+
     let client = match pool.get().await {
         Ok(client) => client,
         Err(err) => {
@@ -238,25 +448,90 @@ async fn login(
         }
     };
 
-    if true != user::User::authenticate(&**client, &login_data.id, &login_data.password).await {
-        log::debug!("unable to authenticate user");
-        return Ok(HttpResponse::InternalServerError().json("unable to authenticate user"));
+    let user_id = match (&login_data.id, &login_data.login) {
+        (Some(id), _) => uuid::Uuid::parse_str(id).ok(),
+        (None, Some(login)) => match user::User::get_id_by_login(&**client, login).await {
+            Ok(id) => id,
+            Err(err) => {
+                log::debug!("unable to fetch user by login: {:?}", err);
+                return Ok(HttpResponse::InternalServerError().json("unable to authenticate user"));
+            }
+        },
+        (None, None) => None,
+    };
+    let throttle = login_throttle::LoginThrottle::new(user_id.as_ref(), &account, &client_ip);
+
+    // Throttling fails open: a Redis outage must not lock everybody out
+    let mut redis_connection = match redis_pool.get().await {
+        Ok(connection) => Some(connection),
+        Err(err) => {
+            log::error!("unable to get redis client, login throttling is disabled: {:?}", err);
+            None
+        }
+    };
+
+    if let Some(connection) = redis_connection.as_mut() {
+        match throttle.get_lockout_seconds(connection).await {
+            Ok(Some(seconds)) => {
+                log::debug!("unable to authenticate user: too many failed attempts");
+                return Ok(HttpResponse::TooManyRequests()
+                    .insert_header(("Retry-After", seconds.to_string()))
+                    .json("too many failed login attempts, retry in ".to_owned() + seconds.to_string().as_str() + " seconds"));
+            }
+            Ok(None) => (),
+            Err(err) => log::error!("unable to check login lockout: {:?}", err),
+        }
+    }
+
+    let is_authenticated = match &user_id {
+        Some(user_id) => user::User::authenticate(&**client, &user_id.to_string(), &login_data.password).await,
+        None => false,
+    };
+
+    if let Some(connection) = redis_connection.as_mut() {
+        let result = if is_authenticated {
+            throttle.register_success(connection).await
+        } else {
+            throttle.register_failure(connection).await
+        };
+        if let Err(err) = result {
+            log::error!("unable to update login throttle: {:?}", err);
+        }
     }
 
-    match session::Session::create(&session::Session::new(
+    let user_id = match user_id {
+        Some(user_id) if is_authenticated => user_id,
+        _ => {
+            log::debug!("unable to authenticate user");
+            return Ok(HttpResponse::Unauthorized().json("unable to authenticate user"));
+        }
+    };
+
+    let user_agent = req
+        .headers()
+        .get(actix_web::http::header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("unknown");
+    let session = session::Session::new(
         uuid::Uuid::new_v4().to_string(),
-        login_data.id,
-        serde_json::json!({}).to_string(),
-    ))
-    .await
-    {
+        user_id.to_string(),
+        serde_json::json!({
+            "user_agent": user_agent,
+            "ip": client_ip,
+        }).to_string(),
+    );
+
+    match session::Session::create(&session).await {
         Ok(session_id) => {
             #[derive(Debug, Serialize)]
             struct UserLoginResponse {
                 token: String,
+                #[serde(skip_serializing_if = "Option::is_none")]
+                access_token: Option<String>,
             }
             Ok(HttpResponse::Ok().json(UserLoginResponse {
                 token: session_id.to_string(),
+                access_token: access_token::issue(&session),
             }))
         }
         Err(err) => {
@@ -266,74 +541,1104 @@ async fn login(
     }
 }
 
-struct UserSearchService {
-    pg_pool: &'static PostgresPool,
+async fn logout(user: auth::AuthenticatedUser) -> Result<HttpResponse, Error> {
+    match session::Session::delete(&user.get_session_id()).await {
+        Ok(_res) => Ok(HttpResponse::Ok().json("ok")),
+        Err(err) => {
+            log::debug!("unable to logout: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to logout"))
+        }
+    }
 }
 
-#[tonic::async_trait]
-impl UserSearch for UserSearchService {
-    async fn search(
-        &self,
-        request: tonic::Request<UserSearchRequest>,
-    ) -> std::result::Result<tonic::Response<UserSearchResponse>, tonic::Status> {
-        let client = match self.pg_pool.get().await {
-            Ok(client) => client,
-            Err(err) => {
-                log::debug!("unable to get postgres client: {:?}", err);
-                return Err(tonic::Status::not_found("unable to get postgres client"));
-            }
-        };
+#[derive(Debug, Serialize)]
+struct UserSessionResponse {
+    id: Uuid,
+    user_agent: Option<String>,
+    ip: Option<String>,
+    time_created: chrono::NaiveDateTime,
+    time_last_seen: chrono::NaiveDateTime,
+    is_current: bool,
+}
 
-        let req = request.into_inner().to_owned();
+async fn user_sessions(user: auth::AuthenticatedUser) -> Result<HttpResponse, Error> {
+    match session::Session::get_by_user_id(&user.get_user_id()).await {
+        Ok(sessions) => Ok(HttpResponse::Ok().json(
+            sessions
+                .iter()
+                .map(|session| UserSessionResponse {
+                    id: session.get_id(),
+                    user_agent: session.get_data()["user_agent"].as_str().map(String::from),
+                    ip: session.get_data()["ip"].as_str().map(String::from),
+                    time_created: *session.get_time_created(),
+                    time_last_seen: *session.get_time_updated(),
+                    is_current: session.get_id() == user.get_session_id(),
+                })
+                .collect::<Vec<UserSessionResponse>>(),
+        )),
+        Err(err) => {
+            log::debug!("unable to fetch sessions: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to fetch sessions"))
+        }
+    }
+}
 
-        let first_name = &req.first_name;
-        let second_name = &req.last_name;
+async fn user_session_delete(
+    path: web::Path<String>,
+    user: auth::AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    // Sessions of other users are reported as missing to avoid leaking their ids
+    let session = match session::Session::read_by_id(&path).await {
+        Ok(Some(session)) if user.is_owner_of(&session.get_user_id()) => session,
+        Ok(_) => return Ok(HttpResponse::NotFound().json("session is not found")),
+        Err(err) => {
+            log::debug!("unable to fetch session: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to fetch session"));
+        }
+    };
 
-        match user::User::search_by_first_name_and_last_name(
-            &**client,
-            &first_name,
-            &second_name,
-            true,
-        )
-        .await
-        {
-            Ok(users) => {
-                let user_data = users
-                    .into_iter()
-                    .map(|user: user::User| user_search::User {
-                        id: user.id().to_string(),
-                        first_name: user.first_name().to_string(),
-                        second_name: user.second_name().to_string(),
-                        birthdate: user.birthdate().to_string(),
-                        biography: user.biography().to_string(),
-                        city: user.city().to_string(),
-                    })
-                    .collect();
-                Ok(tonic::Response::new(UserSearchResponse {
-                    data: user_data,
-                    page_number: 1,
-                    results_per_page: 50,
-                }))
-            }
-            Err(err) => {
-                log::debug!("unable to find users: {:?}", err);
-                return Err(tonic::Status::not_found(format!(
-                    "Couldn't find users by query: {}, {}",
-                    &first_name, &second_name
-                )));
-            }
+    match session::Session::delete(&session.get_id()).await {
+        Ok(_res) => Ok(HttpResponse::Ok().json("ok")),
+        Err(err) => {
+            log::debug!("unable to delete session: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to delete session"))
+        }
+    }
+}
+
+async fn logout_all(user: auth::AuthenticatedUser) -> Result<HttpResponse, Error> {
+    match session::Session::delete_by_user_id(&user.get_user_id()).await {
+        Ok(_res) => Ok(HttpResponse::Ok().json("ok")),
+        Err(err) => {
+            log::debug!("unable to revoke sessions: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to revoke sessions"))
+        }
+    }
+}
+
+async fn user_update(
+    pool: web::Data<&'static PostgresPool>,
+    mut payload: web::Payload,
+    user: auth::AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if (body.len() + chunk.len()) > MAX_SIZE {
+            return Err(error::ErrorBadRequest("overflow"));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct UserUpdatePayload {
+        first_name: Option<String>,
+        second_name: Option<String>,
+        birthdate: Option<String>,
+        biography: Option<String>,
+        city: Option<String>,
+    }
+
+    let user_data = match serde_json::from_slice::<UserUpdatePayload>(&body) {
+        Ok(user_data) => user_data,
+        Err(err) => {
+            log::debug!("unable to parse json data: {:?}", err);
+            return Ok(HttpResponse::BadRequest().json("unable to parse json data"));
+        }
+    };
+
+    let update = match user::UserUpdate::new(
+        user_data.first_name.as_ref(),
+        user_data.second_name.as_ref(),
+        user_data.birthdate.as_ref(),
+        user_data.biography.as_ref(),
+        user_data.city.as_ref(),
+    ) {
+        Ok(update) => update,
+        Err(errors) => {
+            log::debug!("unable to update user: {:?}", errors);
+            return Ok(HttpResponse::BadRequest().json(json!({ "errors": errors })));
+        }
+    };
+
+    let client = match pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get postgres client: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get postgres client"));
+        }
+    };
+
+    match user::User::update(&**client, &user.get_user_id(), &update).await {
+        Ok(Some(updated_user)) => Ok(HttpResponse::Ok().json(updated_user)),
+        Ok(None) => Ok(HttpResponse::NotFound().json("user not found")),
+        Err(err) => {
+            log::debug!("unable to update user: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to update user"))
+        }
+    }
+}
+
+async fn user_delete(
+    pool: web::Data<&'static PostgresPool>,
+    mut payload: web::Payload,
+    user: auth::AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if (body.len() + chunk.len()) > MAX_SIZE {
+            return Err(error::ErrorBadRequest("overflow"));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct UserDeletePayload {
+        password: String,
+    }
+
+    let delete_data = match serde_json::from_slice::<UserDeletePayload>(&body) {
+        Ok(delete_data) => delete_data,
+        Err(err) => {
+            log::debug!("unable to parse json data: {:?}", err);
+            return Ok(HttpResponse::BadRequest().json("unable to parse json data"));
+        }
+    };
+
+    let mut client = match pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get postgres client: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get postgres client"));
+        }
+    };
+
+    if true != user::User::authenticate(&**client, &user.get_user_id().to_string(), &delete_data.password).await {
+        log::debug!("unable to delete user: password is incorrect");
+        return Ok(HttpResponse::Forbidden().json("unable to delete user: password is incorrect"));
+    }
+
+    if let Err(err) = account_deletion::schedule(&mut **client, &user.get_user_id()).await {
+        log::debug!("unable to delete user: {:?}", err);
+        return Ok(HttpResponse::InternalServerError().json("unable to delete user"));
+    }
+
+    // Tokens stop working before the response; the background step retries if the session store is down
+    if let Err(err) = session::Session::delete_by_user_id(&user.get_user_id()).await {
+        log::warn!("unable to revoke sessions of deleted user, left to background cleanup: {:?}", err);
+    }
+
+    // Friends, feed caches and dialogs are cleaned up in background
+    account_deletion::process_now(user.get_user_id());
+
+    Ok(HttpResponse::Accepted().json("ok"))
+}
+
+async fn user_export(
+    pool: web::Data<&'static PostgresPool>,
+    user: auth::AuthenticatedUser,
+) -> HttpResponse {
+    let client = match pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get postgres client: {:?}", err);
+            return HttpResponse::InternalServerError().json("unable to get postgres client");
+        }
+    };
+
+    match user_export::get_or_start(&**client, &user.get_user_id()).await {
+        Ok(user_export::UserExportState::Ready(archive)) => HttpResponse::Ok()
+            .content_type("application/zip")
+            .insert_header(("Content-Disposition", "attachment; filename=\"export.zip\""))
+            .body(archive),
+        Ok(user_export::UserExportState::Pending) => HttpResponse::Accepted().json("export is being prepared, try again later"),
+        Err(err) => {
+            log::debug!("unable to export user data: {:?}", err);
+            HttpResponse::InternalServerError().json("unable to export user data")
+        }
+    }
+}
+
+async fn user_password_change(
+    pool: web::Data<&'static PostgresPool>,
+    mut payload: web::Payload,
+    user: auth::AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if (body.len() + chunk.len()) > MAX_SIZE {
+            return Err(error::ErrorBadRequest("overflow"));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct PasswordChangePayload {
+        old_password: String,
+        new_password: String,
+    }
+
+    let password_data = match serde_json::from_slice::<PasswordChangePayload>(&body) {
+        Ok(password_data) => password_data,
+        Err(err) => {
+            log::debug!("unable to parse json data: {:?}", err);
+            return Ok(HttpResponse::BadRequest().json("unable to parse json data"));
+        }
+    };
+
+    if let Err(err) = user::User::is_password_correct(&password_data.new_password) {
+        log::debug!("unable to change password: {:?}", err);
+        return Ok(HttpResponse::BadRequest()
+            .json("password format is incorrect: ".to_owned() + &err.to_string()));
+    }
+
+    let client = match pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get postgres client: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get postgres client"));
+        }
+    };
+
+    if true != user::User::authenticate(&**client, &user.get_user_id().to_string(), &password_data.old_password).await {
+        log::debug!("unable to change password: old password is incorrect");
+        return Ok(HttpResponse::Forbidden().json("unable to change password: old password is incorrect"));
+    }
+
+    if let Err(err) = user::User::update_password(&**client, &user.get_user_id(), &password_data.new_password).await {
+        log::debug!("unable to change password: {:?}", err);
+        return Ok(HttpResponse::InternalServerError().json("unable to change password"));
+    }
+
+    match session::Session::delete_by_user_id_except(&user.get_user_id(), &user.get_session_id()).await {
+        Ok(_res) => Ok(HttpResponse::Ok().json("ok")),
+        Err(err) => {
+            log::debug!("unable to revoke sessions: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to revoke sessions"))
+        }
+    }
+}
+
+async fn user_password_reset_request(
+    pool: web::Data<&'static PostgresPool>,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, Error> {
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if (body.len() + chunk.len()) > MAX_SIZE {
+            return Err(error::ErrorBadRequest("overflow"));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct PasswordResetRequestPayload {
+        id: Option<String>,
+        login: Option<String>,
+    }
+
+    let reset_data = match serde_json::from_slice::<PasswordResetRequestPayload>(&body) {
+        Ok(reset_data) => reset_data,
+        Err(err) => {
+            log::debug!("unable to parse json data: {:?}", err);
+            return Ok(HttpResponse::BadRequest().json("unable to parse json data"));
+        }
+    };
+
+    let client = match pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get postgres client: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get postgres client"));
+        }
+    };
+
+    let user_id = match (&reset_data.id, &reset_data.login) {
+        (Some(id), _) => Uuid::parse_str(id).ok(),
+        (None, Some(login)) => match user::User::get_id_by_login(&**client, login).await {
+            Ok(user_id) => user_id,
+            Err(err) => {
+                log::debug!("unable to fetch user by login: {:?}", err);
+                return Ok(HttpResponse::InternalServerError().json("unable to request password reset"));
+            }
+        },
+        (None, None) => {
+            return Ok(HttpResponse::BadRequest().json("either id or login should be specified"));
+        }
+    };
+
+    // Respond the same way whether the user exists or not, so accounts can't be enumerated
+    if let Some(user_id) = user_id {
+        if let Ok(user) = user::User::get_by_id(&**client, &user_id.to_string()).await {
+            if let Err(err) = password_reset::PasswordResetToken::issue(&**client, &user).await {
+                log::debug!("unable to issue password reset token: {:?}", err);
+                return Ok(HttpResponse::InternalServerError().json("unable to request password reset"));
+            }
+        }
+    }
+
+    Ok(HttpResponse::Ok().json("ok"))
+}
+
+async fn user_password_reset_confirm(
+    pool: web::Data<&'static PostgresPool>,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, Error> {
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if (body.len() + chunk.len()) > MAX_SIZE {
+            return Err(error::ErrorBadRequest("overflow"));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct PasswordResetConfirmPayload {
+        token: String,
+        new_password: String,
+    }
+
+    let reset_data = match serde_json::from_slice::<PasswordResetConfirmPayload>(&body) {
+        Ok(reset_data) => reset_data,
+        Err(err) => {
+            log::debug!("unable to parse json data: {:?}", err);
+            return Ok(HttpResponse::BadRequest().json("unable to parse json data"));
+        }
+    };
+
+    if let Err(err) = user::User::is_password_correct(&reset_data.new_password) {
+        log::debug!("unable to reset password: {:?}", err);
+        return Ok(HttpResponse::BadRequest()
+            .json("password format is incorrect: ".to_owned() + &err.to_string()));
+    }
+
+    let client = match pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get postgres client: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get postgres client"));
+        }
+    };
+
+    let user_id = match password_reset::PasswordResetToken::redeem(&**client, &reset_data.token).await {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => {
+            log::debug!("unable to reset password: token is invalid, expired or already used");
+            return Ok(HttpResponse::BadRequest().json("password reset token is invalid or expired"));
+        }
+        Err(err) => {
+            log::debug!("unable to redeem password reset token: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to reset password"));
+        }
+    };
+
+    if let Err(err) = user::User::update_password(&**client, &user_id, &reset_data.new_password).await {
+        log::debug!("unable to reset password: {:?}", err);
+        return Ok(HttpResponse::InternalServerError().json("unable to reset password"));
+    }
+
+    match session::Session::delete_by_user_id(&user_id).await {
+        Ok(_res) => Ok(HttpResponse::Ok().json("ok")),
+        Err(err) => {
+            log::debug!("unable to revoke sessions: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to revoke sessions"))
+        }
+    }
+}
+
+fn to_grpc_user(user: user::User) -> user_search::User {
+    user_search::User {
+        id: user.id().to_string(),
+        first_name: user.first_name().to_string(),
+        second_name: user.second_name().to_string(),
+        birthdate: user.birthdate().to_string(),
+        biography: user.biography().to_string(),
+        city: user.city().to_string(),
+    }
+}
+
+fn parse_grpc_uuid(field: &str, value: &str) -> Result<Uuid, tonic::Status> {
+    Uuid::parse_str(value).map_err(|_| tonic::Status::invalid_argument(format!("{} is malformed", field)))
+}
+
+// Friends and feed are served to their owner only, an empty user_id stands for the caller
+fn get_grpc_owner_id(caller_id: &Uuid, user_id: &str) -> Result<Uuid, tonic::Status> {
+    if user_id.is_empty() {
+        return Ok(*caller_id);
+    }
+
+    let user_id = parse_grpc_uuid("user_id", user_id)?;
+    if user_id != *caller_id {
+        return Err(tonic::Status::permission_denied("user_id doesn't match the authenticated user"));
+    }
+    Ok(user_id)
+}
+
+struct UserSearchService {
+    pg_pool: &'static PostgresPool,
+}
+
+#[tonic::async_trait]
+impl UserSearch for UserSearchService {
+    async fn search(
+        &self,
+        request: tonic::Request<UserSearchRequest>,
+    ) -> std::result::Result<tonic::Response<UserSearchResponse>, tonic::Status> {
+        let client = match self.pg_pool.get().await {
+            Ok(client) => client,
+            Err(err) => {
+                log::debug!("unable to get postgres client: {:?}", err);
+                return Err(tonic::Status::not_found("unable to get postgres client"));
+            }
+        };
+
+        let req = request.into_inner().to_owned();
+
+        let first_name = &req.first_name;
+        let second_name = &req.last_name;
+
+        let mode = match user::UserSearchMode::from_str(&req.mode) {
+            Ok(mode) => mode,
+            Err(err) => return Err(tonic::Status::invalid_argument(err.to_string())),
+        };
+        if first_name.trim().is_empty() && second_name.trim().is_empty() {
+            return Err(tonic::Status::invalid_argument("first_name or last_name is required"));
+        }
+
+        match user::User::search_page(
+            &**client,
+            &first_name,
+            &second_name,
+            mode,
+            req.page_number as usize,
+            req.results_per_page as usize,
+        )
+        .await
+        {
+            Ok(page) => {
+                let user_data = page.users.into_iter().map(to_grpc_user).collect();
+                Ok(tonic::Response::new(UserSearchResponse {
+                    data: user_data,
+                    page_number: page.page_number as u32,
+                    results_per_page: page.results_per_page as u32,
+                    has_next_page: page.has_next_page,
+                }))
+            }
+            Err(err) => {
+                log::debug!("unable to find users: {:?}", err);
+                return Err(tonic::Status::not_found(format!(
+                    "Couldn't find users by query: {}, {}",
+                    &first_name, &second_name
+                )));
+            }
+        }
+    }
+}
+
+struct UserApiService {
+    pg_pool: &'static PostgresPool,
+    redis_pool: &'static RedisPool,
+}
+
+#[tonic::async_trait]
+impl UserService for UserApiService {
+    async fn get_user(
+        &self,
+        request: tonic::Request<GetUserRequest>,
+    ) -> std::result::Result<tonic::Response<GetUserResponse>, tonic::Status> {
+        let caller_id = auth::authenticate_grpc(&request).await?;
+        let id = parse_grpc_uuid("id", &request.into_inner().id)?;
+
+        // Blocked users don't see each other, same as in the feed and dialogs
+        match user_block::UserBlock::is_blocked_between(&caller_id, &id).await {
+            Ok(false) => (),
+            Ok(true) => return Err(tonic::Status::not_found(format!("Couldn't find user: {}", id))),
+            Err(err) => {
+                log::debug!("unable to get user blocks: {:?}", err);
+                return Err(tonic::Status::unavailable("unable to get user blocks"));
+            }
+        }
+
+        let client = match self.pg_pool.get().await {
+            Ok(client) => client,
+            Err(err) => {
+                log::debug!("unable to get postgres client: {:?}", err);
+                return Err(tonic::Status::unavailable("unable to get postgres client"));
+            }
+        };
+
+        let user = match user::User::get_by_id(&**client, &id.to_string()).await {
+            Ok(user) => user,
+            Err(err) => {
+                log::debug!("unable to fetch user: {:?}", err);
+                return Err(tonic::Status::not_found(format!("Couldn't find user: {}", id)));
+            }
+        };
+
+        match friend::Friend::get_counts(&id).await {
+            Ok(counts) => Ok(tonic::Response::new(GetUserResponse {
+                user: Some(to_grpc_user(user)),
+                followers_count: counts.get_followers_count(),
+                following_count: counts.get_following_count(),
+            })),
+            Err(err) => {
+                log::debug!("unable to fetch friend counts: {:?}", err);
+                Err(tonic::Status::unavailable("unable to fetch friend counts"))
+            }
+        }
+    }
+
+    async fn batch_get_users(
+        &self,
+        request: tonic::Request<BatchGetUsersRequest>,
+    ) -> std::result::Result<tonic::Response<BatchGetUsersResponse>, tonic::Status> {
+        let caller_id = auth::authenticate_grpc(&request).await?;
+        let ids = request.into_inner().ids;
+        if USER_BATCH_MAX_IDS < ids.len() {
+            return Err(tonic::Status::invalid_argument(format!("no more than {} ids are allowed", USER_BATCH_MAX_IDS)));
+        }
+        let ids = ids.iter().map(|id| parse_grpc_uuid("ids", id)).collect::<Result<Vec<Uuid>, _>>()?;
+
+        // Blocked users are reported as not found, same as in GetUser
+        let hidden_user_ids = match user_block::UserBlock::get_hidden_user_ids(&caller_id).await {
+            Ok(ids) => ids,
+            Err(err) => {
+                log::debug!("unable to get user blocks: {:?}", err);
+                return Err(tonic::Status::unavailable("unable to get user blocks"));
+            }
+        };
+        let visible_ids: Vec<Uuid> = ids.iter().filter(|id| !hidden_user_ids.contains(id)).copied().collect();
+
+        let client = match self.pg_pool.get().await {
+            Ok(client) => client,
+            Err(err) => {
+                log::debug!("unable to get postgres client: {:?}", err);
+                return Err(tonic::Status::unavailable("unable to get postgres client"));
+            }
+        };
+
+        match user::User::get_by_ids(&**client, &visible_ids).await {
+            Ok(users) => {
+                let not_found_ids = get_not_found_ids(&ids, &users).iter().map(Uuid::to_string).collect();
+                Ok(tonic::Response::new(BatchGetUsersResponse {
+                    users: users.into_iter().map(to_grpc_user).collect(),
+                    not_found_ids,
+                }))
+            }
+            Err(err) => {
+                log::debug!("unable to fetch users: {:?}", err);
+                Err(tonic::Status::internal("unable to fetch users"))
+            }
+        }
+    }
+
+    async fn get_friends(
+        &self,
+        request: tonic::Request<GetFriendsRequest>,
+    ) -> std::result::Result<tonic::Response<GetFriendsResponse>, tonic::Status> {
+        let caller_id = auth::authenticate_grpc(&request).await?;
+        let user_id = get_grpc_owner_id(&caller_id, &request.into_inner().user_id)?;
+
+        match friend::Friend::get_by_user_id(&user_id).await {
+            Ok(friends) => Ok(tonic::Response::new(GetFriendsResponse {
+                friends: friends
+                    .into_iter()
+                    .map(|friend| user_search::Friend {
+                        id: friend.get_id().to_string(),
+                        user_id: friend.get_user_id().to_string(),
+                        friend_id: friend.get_friend_id().to_string(),
+                    })
+                    .collect(),
+            })),
+            Err(err) => {
+                log::debug!("unable to get friends: {:?}", err);
+                Err(tonic::Status::internal("unable to get friends"))
+            }
+        }
+    }
+
+    async fn get_feed(
+        &self,
+        request: tonic::Request<GetFeedRequest>,
+    ) -> std::result::Result<tonic::Response<GetFeedResponse>, tonic::Status> {
+        let caller_id = auth::authenticate_grpc(&request).await?;
+        let req = request.into_inner();
+        let user_id = get_grpc_owner_id(&caller_id, &req.user_id)?;
+        if 0 == req.limit {
+            return Err(tonic::Status::invalid_argument("limit must be positive"));
+        }
+
+        let pg_client = match self.pg_pool.get().await {
+            Ok(client) => client,
+            Err(err) => {
+                log::debug!("unable to get postgres client: {:?}", err);
+                return Err(tonic::Status::unavailable("unable to get postgres client"));
+            }
+        };
+
+        let mut redis_connection = match self.redis_pool.get().await {
+            Ok(client) => client,
+            Err(err) => {
+                log::debug!("unable to get redis client: {:?}", err);
+                return Err(tonic::Status::unavailable("unable to get redis client"));
+            }
+        };
+
+        let hidden_user_ids = match user_block::UserBlock::get_hidden_user_ids(&user_id).await {
+            Ok(ids) => ids,
+            Err(err) => {
+                log::debug!("unable to get user blocks: {:?}", err);
+                return Err(tonic::Status::unavailable("unable to get user blocks"));
+            }
+        };
+
+        match post::Post::get_feed(
+            &**pg_client,
+            &mut redis_connection,
+            &user_id,
+            &hidden_user_ids,
+            &(req.offset as usize),
+            &(req.limit as usize),
+        )
+        .await
+        {
+            Ok(feed) => Ok(tonic::Response::new(GetFeedResponse {
+                posts: feed
+                    .into_iter()
+                    .map(|post| user_search::Post {
+                        id: post.get_id().to_string(),
+                        user_id: post.get_user_id().to_string(),
+                        content: post.get_content().to_string(),
+                        time_created: post.get_time_created().and_utc().to_rfc3339(),
+                        time_updated: post.get_time_updated().and_utc().to_rfc3339(),
+                    })
+                    .collect(),
+            })),
+            Err(err) => {
+                log::debug!("unable to get feed: {:?}", err);
+                Err(tonic::Status::internal("unable to get feed"))
+            }
+        }
+    }
+}
+
+// Served next to the HTTP server when GRPC_SERVER_ENABLED=true, with the same certificate unless GRPC_SERVER_TLS=false
+async fn serve_grpc() -> std::io::Result<()> {
+    if "true" != std::env::var("GRPC_SERVER_ENABLED").unwrap_or_default() {
+        return Ok(());
+    }
+
+    let grpc_address = std::env::var("GRPC_SERVER_ADDRESS")
+        .unwrap_or_else(|_| "127.0.0.1:9000".into())
+        .parse::<std::net::SocketAddr>()
+        .unwrap_or_else(|_| std::net::SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1)), 9000));
+
+    let mut grpc_server = tonic::transport::Server::builder();
+    if "false" != std::env::var("GRPC_SERVER_TLS").unwrap_or_default() {
+        grpc_server = grpc_server
+            .tls_config(tonic::transport::server::ServerTlsConfig::new().identity(
+                tonic::transport::Identity::from_pem(&std::fs::read_to_string("cert.pem")?,
+                &std::fs::read_to_string("key.pem")?))
+            )
+            .map_err(std::io::Error::other)?;
+    }
+
+    let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
+    health_reporter.set_serving::<UserSearchServer<UserSearchService>>().await;
+    health_reporter.set_serving::<UserServiceServer<UserApiService>>().await;
+
+    let reflection_service = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(user_search::FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+        .build()
+        .map_err(std::io::Error::other)?;
+
+    log::info!("gRPC server is listening on {}", grpc_address);
+    grpc_server
+        .add_service(health_service)
+        .add_service(reflection_service)
+        .add_service(
+            UserSearchServer::new(UserSearchService { pg_pool: postgres::get_replica_pool_ref() })
+                .send_compressed(CompressionEncoding::Gzip)
+                .accept_compressed(CompressionEncoding::Gzip)
+        )
+        .add_service(
+            UserServiceServer::new(UserApiService {
+                pg_pool: postgres::get_replica_pool_ref(),
+                redis_pool: redis::get_pool_ref(),
+            })
+                .send_compressed(CompressionEncoding::Gzip)
+                .accept_compressed(CompressionEncoding::Gzip)
+        )
+        .serve(grpc_address)
+        .await
+        .map_err(std::io::Error::other)
+}
+
+// Friendship is mutual, so adding a friend only sends a request that the other user has to accept
+async fn friend_request_send(
+    path: web::Path<String>,
+    user: auth::AuthenticatedUser,
+    pg_pool: web::Data<&'static PostgresPool>,
+) -> Result<HttpResponse, Error> {
+    let to_user_id = match uuid::Uuid::from_str(&path.into_inner()) {
+        Ok(val) => val,
+        Err(err) => {
+            log::debug!("unable to parse user id: {:?}", err);
+            return Ok(HttpResponse::BadRequest().json("unable to parse user id"));
+        }
+    };
+
+    let from_user_id = user.get_user_id();
+    if from_user_id == to_user_id {
+        return Ok(HttpResponse::BadRequest().json("unable to send friend request to yourself"));
+    }
+
+    let pg_client = match pg_pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get postgres client: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get postgres client"));
+        }
+    };
+
+    match user::User::get_by_ids(&**pg_client, &[to_user_id]).await {
+        Ok(users) if users.is_empty() => return Ok(HttpResponse::NotFound().json("user not found")),
+        Ok(_) => (),
+        Err(err) => {
+            log::debug!("unable to send friend request: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to send friend request"));
+        }
+    }
+
+    match user_block::UserBlock::is_blocked_between(&from_user_id, &to_user_id).await {
+        Ok(false) => (),
+        Ok(true) => return Ok(HttpResponse::Forbidden().json("unable to send friend request: user is blocked")),
+        Err(err) => {
+            log::debug!("unable to send friend request: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to send friend request"));
+        }
+    }
+
+    match friend::Friend::get_by_user_id_and_friend_id(&from_user_id, &to_user_id).await {
+        Ok(Some(_)) => return Ok(HttpResponse::Conflict().json("unable to send friend request: already friends")),
+        Ok(None) => (),
+        Err(err) => {
+            log::debug!("unable to send friend request: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to send friend request"));
+        }
+    }
+
+    for (from, to) in [(&from_user_id, &to_user_id), (&to_user_id, &from_user_id)] {
+        match friend_request::FriendRequest::get_pending(from, to).await {
+            Ok(Some(_)) => return Ok(HttpResponse::Conflict().json("unable to send friend request: request is already pending")),
+            Ok(None) => (),
+            Err(err) => {
+                log::debug!("unable to send friend request: {:?}", err);
+                return Ok(HttpResponse::InternalServerError().json("unable to send friend request"));
+            }
+        }
+    }
+
+    let request = friend_request::FriendRequest::new(from_user_id, to_user_id);
+    match friend_request::FriendRequest::create(&request).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(request)),
+        Err(err) => {
+            log::debug!("unable to send friend request: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to send friend request"))
+        }
+    }
+}
+
+async fn friend_requests_incoming(
+    user: auth::AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    match friend_request::FriendRequest::get_incoming(&user.get_user_id()).await {
+        Ok(requests) => Ok(HttpResponse::Ok().json(requests)),
+        Err(err) => {
+            log::debug!("unable to get friend requests: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to get friend requests"))
+        }
+    }
+}
+
+async fn friend_requests_outgoing(
+    user: auth::AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    match friend_request::FriendRequest::get_outgoing(&user.get_user_id()).await {
+        Ok(requests) => Ok(HttpResponse::Ok().json(requests)),
+        Err(err) => {
+            log::debug!("unable to get friend requests: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to get friend requests"))
+        }
+    }
+}
+
+async fn friend_request_accept(
+    path: web::Path<String>,
+    user: auth::AuthenticatedUser,
+    redis_pool: web::Data<&'static RedisPool>,
+) -> Result<HttpResponse, Error> {
+    friend_request_resolve(path.into_inner(), user, *redis_pool.get_ref(), friend_request::FriendRequestStatus::Accepted).await
+}
+
+async fn friend_request_decline(
+    path: web::Path<String>,
+    user: auth::AuthenticatedUser,
+    redis_pool: web::Data<&'static RedisPool>,
+) -> Result<HttpResponse, Error> {
+    friend_request_resolve(path.into_inner(), user, *redis_pool.get_ref(), friend_request::FriendRequestStatus::Declined).await
+}
+
+async fn friend_request_cancel(
+    path: web::Path<String>,
+    user: auth::AuthenticatedUser,
+    redis_pool: web::Data<&'static RedisPool>,
+) -> Result<HttpResponse, Error> {
+    friend_request_resolve(path.into_inner(), user, *redis_pool.get_ref(), friend_request::FriendRequestStatus::Cancelled).await
+}
+
+// Only the recipient accepts or declines a request, only the sender cancels it
+async fn friend_request_resolve(
+    request_id: String,
+    user: auth::AuthenticatedUser,
+    redis_pool: &'static RedisPool,
+    status: friend_request::FriendRequestStatus,
+) -> Result<HttpResponse, Error> {
+    let request_id = match uuid::Uuid::from_str(&request_id) {
+        Ok(val) => val,
+        Err(err) => {
+            log::debug!("unable to parse friend request id: {:?}", err);
+            return Ok(HttpResponse::BadRequest().json("unable to parse friend request id"));
+        }
+    };
+
+    let mut request = match friend_request::FriendRequest::get_by_id(&request_id).await {
+        Ok(Some(request)) => request,
+        Ok(None) => return Ok(HttpResponse::NotFound().json("friend request not found")),
+        Err(err) => {
+            log::debug!("unable to get friend request: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get friend request"));
+        }
+    };
+
+    let allowed_user_id = match status {
+        friend_request::FriendRequestStatus::Cancelled => request.get_from_user_id(),
+        _ => request.get_to_user_id(),
+    };
+    if user.get_user_id() != allowed_user_id {
+        return Ok(HttpResponse::NotFound().json("friend request not found"));
+    }
+
+    if request.get_status() != friend_request::FriendRequestStatus::Pending {
+        return Ok(HttpResponse::Conflict().json(format!("friend request is already {}", request.get_status())));
+    }
+
+    request.set_status(status);
+    match friend_request::FriendRequest::resolve(&request).await {
+        Ok(true) => (),
+        Ok(false) => return Ok(HttpResponse::Conflict().json("friend request is not pending anymore")),
+        Err(err) => {
+            log::debug!("unable to update friend request: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to update friend request"));
+        }
+    }
+
+    if status == friend_request::FriendRequestStatus::Accepted {
+        // Both feeds now include the other user's posts
+        let mut redis_connection = match redis_pool.get().await {
+            Ok(client) => client,
+            Err(err) => {
+                log::debug!("unable to get redis client: {:?}", err);
+                return Ok(HttpResponse::InternalServerError().json("unable to get redis client"));
+            }
+        };
+        for user_id in [request.get_from_user_id(), request.get_to_user_id()] {
+            let cache_key = post::FEED_CACHE_KEY_PREFIX.to_string() + user_id.to_string().as_str();
+            if let Err(err) = redis::del(&cache_key, &mut redis_connection).await {
+                log::warn!("unable to invalidate feed cache '{}': {:?}", cache_key, err);
+            }
+        }
+        if let Err(err) = friend_suggestion::invalidate(&mut redis_connection, &[request.get_from_user_id(), request.get_to_user_id()]).await {
+            log::warn!("unable to invalidate friend suggestions: {:?}", err);
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(request))
+}
+
+#[derive(Deserialize)]
+struct FriendSearchQuery {
+    #[serde(default)]
+    name: String,
+    cursor: Option<Uuid>,
+    limit: Option<usize>,
+}
+
+async fn friend_search(
+    pg_pool: web::Data<&'static PostgresPool>,
+    user: auth::AuthenticatedUser,
+    search: web::Query<FriendSearchQuery>,
+) -> Result<HttpResponse, Error> {
+    let pg_client = match pg_pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get postgres client: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get postgres client"));
+        }
+    };
+
+    match friend::Friend::get_page(
+        &**pg_client,
+        &user.get_user_id(),
+        &search.name,
+        search.cursor.as_ref(),
+        search.limit.unwrap_or_default(),
+    ).await {
+        Ok(page) => Ok(HttpResponse::Ok().json(page)),
+        Err(err) => {
+            log::debug!("unable to search friend: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to search friends"))
+        }
+    }
+}
+
+async fn friend_suggestions(
+    pg_pool: web::Data<&'static PostgresPool>,
+    redis_pool: web::Data<&'static RedisPool>,
+    user: auth::AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    let pg_client = match pg_pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get postgres client: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get postgres client"));
+        }
+    };
+
+    let mut redis_connection = match redis_pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get redis client: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get redis client"));
+        }
+    };
+
+    match friend_suggestion::get(&**pg_client, &mut redis_connection, &user.get_user_id()).await {
+        Ok(suggestions) => Ok(HttpResponse::Ok().json(suggestions)),
+        Err(err) => {
+            log::debug!("unable to get friend suggestions: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to get friend suggestions"))
+        }
+    }
+}
+
+async fn friend_mutual(
+    path: web::Path<String>,
+    pg_pool: web::Data<&'static PostgresPool>,
+    user: auth::AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    let other_user_id = match uuid::Uuid::from_str(&path.into_inner()) {
+        Ok(val) => val,
+        Err(err) => {
+            log::debug!("unable to parse user id: {:?}", err);
+            return Ok(HttpResponse::BadRequest().json("unable to parse user id"));
+        }
+    };
+
+    let mutual_friend_ids = match friend::Friend::get_mutual_friend_ids(&user.get_user_id(), &other_user_id).await {
+        Ok(ids) => ids,
+        Err(err) => {
+            log::debug!("unable to get mutual friends: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get mutual friends"));
+        }
+    };
+
+    let pg_client = match pg_pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get postgres client: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get postgres client"));
+        }
+    };
+
+    match user::User::get_by_ids(&**pg_client, &mutual_friend_ids).await {
+        Ok(users) => Ok(HttpResponse::Ok().json(users)),
+        Err(err) => {
+            log::debug!("unable to get mutual friends: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to get mutual friends"))
+        }
+    }
+}
+
+async fn user_block_create(
+    path: web::Path<String>,
+    pg_pool: web::Data<&'static PostgresPool>,
+    redis_pool: web::Data<&'static RedisPool>,
+    user: auth::AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    let blocked_user_id = match uuid::Uuid::from_str(&path.into_inner()) {
+        Ok(val) => val,
+        Err(err) => {
+            log::debug!("unable to parse user id: {:?}", err);
+            return Ok(HttpResponse::BadRequest().json("unable to parse user id"));
+        }
+    };
+    if blocked_user_id == user.get_user_id() {
+        return Ok(HttpResponse::BadRequest().json("unable to block yourself"));
+    }
+
+    let pg_client = match pg_pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get postgres client: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get postgres client"));
+        }
+    };
+
+    match user::User::get_by_ids(&**pg_client, &[blocked_user_id]).await {
+        Ok(users) if users.is_empty() => return Ok(HttpResponse::NotFound().json("user not found")),
+        Ok(_) => (),
+        Err(err) => {
+            log::debug!("unable to block user: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to block user"));
+        }
+    }
+
+    let mut redis_connection = match redis_pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get redis client: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get redis client"));
+        }
+    };
+
+    let block = user_block::UserBlock::new(user.get_user_id(), blocked_user_id);
+    match user_block::UserBlock::block(&block, &mut redis_connection).await {
+        Ok(_) => Ok(HttpResponse::Ok().json("ok")),
+        Err(err) => {
+            log::debug!("unable to block user: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to block user"))
         }
     }
 }
 
-async fn friend_set(
+async fn user_block_delete(
     path: web::Path<String>,
-    auth: BearerAuth,
     redis_pool: web::Data<&'static RedisPool>,
+    user: auth::AuthenticatedUser,
 ) -> Result<HttpResponse, Error> {
-    let friend_user_id = match uuid::Uuid::from_str(&path.parse::<String>().unwrap()) {
+    let blocked_user_id = match uuid::Uuid::from_str(&path.into_inner()) {
         Ok(val) => val,
-        Err(_err) => return Ok(HttpResponse::InternalServerError().json("internal error")),
+        Err(err) => {
+            log::debug!("unable to parse user id: {:?}", err);
+            return Ok(HttpResponse::BadRequest().json("unable to parse user id"));
+        }
     };
 
     let mut redis_connection = match redis_pool.get().await {
@@ -344,81 +1649,43 @@ async fn friend_set(
         }
     };
 
-    if let Ok(Some(session)) = session::Session::get_by_id(auth.token()).await {
-        let user_id = session.get_user_id();
-        let friend = friend::Friend::new(None, user_id, friend_user_id);
-
-        let is_persistant = match friend::Friend::is_persistant(&friend).await {
-            Ok(res) => res,
-            Err(err) => {
-                log::debug!("unable to add friend: {:?}", err);
-                return Ok(HttpResponse::InternalServerError().json("unable to add friend"));
-            }
-        };
-
-        if is_persistant {
-            log::debug!("unable to add friend: record already exists");
-            return Ok(HttpResponse::InternalServerError().json("unable to add friend"));
-        }
-
-        match friend::Friend::create(&friend, &mut redis_connection).await {
-            Ok(_res) => Ok(HttpResponse::Ok().json("ok")),
-            Err(err) => {
-                log::debug!("unable to add friend: {:?}", err);
-                Ok(HttpResponse::InternalServerError().json("unable to add friend"))
-            }
+    match user_block::UserBlock::unblock(&user.get_user_id(), &blocked_user_id, &mut redis_connection).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("ok")),
+        Ok(false) => Ok(HttpResponse::NotFound().json("block not found")),
+        Err(err) => {
+            log::debug!("unable to unblock user: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to unblock user"))
         }
-    } else {
-        log::debug!("unable to add friend: unauthorized");
-        return Ok(HttpResponse::InternalServerError().json("unable to add friend"));
     }
 }
 
-async fn friend_search(
-    pg_pool: web::Data<&'static PostgresPool>,
-    auth: BearerAuth,
+async fn user_block_list(
+    user: auth::AuthenticatedUser,
 ) -> Result<HttpResponse, Error> {
-    if let Ok(Some(session)) = session::Session::get_by_id(auth.token()).await {
-        let user_id = session.get_user_id();
-        let friends = match friend::Friend::get_by_user_id(&user_id).await {
-            Ok(friends) => friends,
-            Err(err) => {
-                log::debug!("unable to search friend: {:?}", err);
-                return Ok(HttpResponse::InternalServerError().json("unable to search friends"));
-            }
-        };
-
-        Ok(HttpResponse::Ok().json(friends))
-
-        // let pg_client = match pg_pool.get().await {
-        //     Ok(client) => client,
-        //     Err(err) => {
-        //         log::debug!("unable to get postgres client: {:?}", err);
-        //         return Ok(HttpResponse::InternalServerError().json("unable to get postgres client"));
-        //     }
-        // };
-
-        // let users = user::User::search_by_ids(
-        //     &**pg_client,
-        //     &friends.into_iter().map(|friend| friend.get_friend_id().to_string()).collect(),
-        // ).await.unwrap();
-
-        // Ok(HttpResponse::Ok().json(users))
-    } else {
-        log::debug!("unable to search friend: unauthorized");
-        return Ok(HttpResponse::InternalServerError().json("unable to search friends"));
+    match user_block::UserBlock::get_by_user_id(&user.get_user_id()).await {
+        Ok(blocks) => Ok(HttpResponse::Ok().json(blocks)),
+        Err(err) => {
+            log::debug!("unable to get user blocks: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to get user blocks"))
+        }
     }
 }
 
 async fn friend_delete(
     path: web::Path<String>,
     mut payload: web::Payload,
+    user: auth::AuthenticatedUser,
     redis_pool: web::Data<&'static RedisPool>,
 ) -> Result<HttpResponse, Error> {
     let user_id = match uuid::Uuid::from_str(&path.parse::<String>().unwrap()) {
         Ok(val) => val,
         Err(_err) => return Ok(HttpResponse::InternalServerError().json("internal error")),
     };
+
+    if !user.is_owner_of(&user_id) {
+        log::debug!("unable to delete friend: user is not owner");
+        return Ok(HttpResponse::Forbidden().json("unable to delete friend: user is not owner"));
+    }
     let mut body = web::BytesMut::new();
     while let Some(chunk) = payload.next().await {
         let chunk = chunk?;
@@ -466,7 +1733,26 @@ async fn friend_delete(
 
     match friend_option {
         Some(friend) => match friend::Friend::delete(&friend, &mut redis_connection).await {
-            Ok(_res) => Ok(HttpResponse::Ok().json("ok")),
+            // Friendship is mutual, the reverse record goes too
+            Ok(_res) => match friend::Friend::get_by_user_id_and_friend_id(&friend_user_id, &user_id).await {
+                Ok(Some(reverse_friend)) => match friend::Friend::delete_relation(&reverse_friend).await {
+                    Ok(_res) => {
+                        if let Err(err) = friend_suggestion::invalidate(&mut redis_connection, &[user_id, friend_user_id]).await {
+                            log::warn!("unable to invalidate friend suggestions: {:?}", err);
+                        }
+                        Ok(HttpResponse::Ok().json("ok"))
+                    }
+                    Err(err) => {
+                        log::debug!("unable to delete friend: {:?}", err);
+                        Ok(HttpResponse::InternalServerError().json("unable to delete friend"))
+                    }
+                },
+                Ok(None) => Ok(HttpResponse::Ok().json("ok")),
+                Err(err) => {
+                    log::debug!("unable to delete friend: {:?}", err);
+                    Ok(HttpResponse::InternalServerError().json("unable to delete friend"))
+                }
+            },
             Err(err) => {
                 log::debug!("unable to add friend: {:?}", err);
                 Ok(HttpResponse::InternalServerError().json("unable to delete friend"))
@@ -482,14 +1768,16 @@ async fn friend_delete(
 
 #[derive(Deserialize)]
 struct PostFeedRequestQuery {
-    offset: usize,
-    limit: usize,
+    // Offset paging is kept for existing clients, without offset the feed is paged by cursor
+    offset: Option<usize>,
+    limit: Option<usize>,
+    cursor: Option<String>,
 }
 
 async fn post_feed(
     pg_pool: web::Data<&'static PostgresPool>,
     search: web::Query<PostFeedRequestQuery>,
-    auth: BearerAuth,
+    user: auth::AuthenticatedUser,
     redis_pool: web::Data<&'static RedisPool>,
 ) -> Result<HttpResponse, Error> {
     let pg_client = match pg_pool.get().await {
@@ -508,14 +1796,22 @@ async fn post_feed(
         }
     };
 
-    if let Ok(Some(session)) = session::Session::get_by_id(auth.token()).await {
-        let user_id = session.get_user_id();
+    let user_id = user.get_user_id();
+    let hidden_user_ids = match user_block::UserBlock::get_hidden_user_ids(&user_id).await {
+        Ok(ids) => ids,
+        Err(err) => {
+            log::debug!("unable to get user blocks: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get user blocks"));
+        }
+    };
+    if let Some(offset) = search.offset {
         let feed = match post::Post::get_feed(
             &**pg_client,
             &mut redis_connection,
             &user_id,
-            &search.offset,
-            &search.limit,
+            &hidden_user_ids,
+            &offset,
+            &search.limit.unwrap_or(post::FEED_PAGE_DEFAULT_LIMIT),
         )
         .await
         {
@@ -526,17 +1822,36 @@ async fn post_feed(
             }
         };
 
-        Ok(HttpResponse::Ok().json(feed))
-    } else {
-        log::debug!("unable to get user id: unauthorized");
-        return Ok(HttpResponse::InternalServerError().json("unable to get user id"));
+        return Ok(HttpResponse::Ok().json(feed));
+    }
+
+    let cursor = match search.cursor.as_deref().map(post::FeedCursor::from_str).transpose() {
+        Ok(cursor) => cursor,
+        Err(err) => return Ok(HttpResponse::BadRequest().json(err.to_string())),
+    };
+
+    match post::Post::get_feed_page(
+        &**pg_client,
+        &mut redis_connection,
+        &user_id,
+        &hidden_user_ids,
+        cursor.as_ref(),
+        search.limit.unwrap_or_default(),
+    )
+    .await
+    {
+        Ok(page) => Ok(HttpResponse::Ok().json(page)),
+        Err(err) => {
+            log::debug!("unable to get feed: {:?}", err);
+            Ok(HttpResponse::InternalServerError().json("unable to get feed"))
+        }
     }
 }
 
 async fn post_create(
     pg_pool: web::Data<&'static PostgresPool>,
     mut payload: web::Payload,
-    auth: BearerAuth,
+    user: auth::AuthenticatedUser,
 ) -> Result<HttpResponse, Error> {
     let mut body = web::BytesMut::new();
     while let Some(chunk) = payload.next().await {
@@ -568,26 +1883,21 @@ async fn post_create(
         }
     };
 
-    if let Ok(Some(session)) = session::Session::get_by_id(auth.token()).await {
-        let user_id = session.get_user_id();
-        let post = match post::Post::new(None, &post_data.text, &user_id) {
-            Ok(post) => post,
-            Err(err) => {
-                log::debug!("unable to create post: {:?}", err);
-                return Ok(HttpResponse::InternalServerError().json("unable to create post"));
-            }
-        };
+    let user_id = user.get_user_id();
+    let post = match post::Post::new(None, &post_data.text, &user_id) {
+        Ok(post) => post,
+        Err(err) => {
+            log::debug!("unable to create post: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to create post"));
+        }
+    };
 
-        match post::Post::create(&**pg_client, &post).await {
-            Ok(_res) => Ok(HttpResponse::Ok().json("ok")),
-            Err(err) => {
-                log::debug!("unable to create post: {:?}", err);
-                return Ok(HttpResponse::InternalServerError().json("unable to create post"));
-            }
+    match post::Post::create(&**pg_client, &post).await {
+        Ok(_res) => Ok(HttpResponse::Ok().json("ok")),
+        Err(err) => {
+            log::debug!("unable to create post: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to create post"));
         }
-    } else {
-        log::debug!("unable to create post: unauthorized");
-        return Ok(HttpResponse::InternalServerError().json("unable to create post"));
     }
 }
 
@@ -623,7 +1933,7 @@ async fn post_update(
     pg_pool: web::Data<&'static PostgresPool>,
     path: web::Path<String>,
     mut payload: web::Payload,
-    auth: BearerAuth,
+    user: auth::AuthenticatedUser,
 ) -> Result<HttpResponse, Error> {
     let post_id = match uuid::Uuid::from_str(&path.parse::<String>().unwrap()) {
         Ok(val) => val,
@@ -660,41 +1970,34 @@ async fn post_update(
         }
     };
 
-    if let Ok(Some(session)) = session::Session::get_by_id(auth.token()).await {
-        let user_id = session.get_user_id();
-        let mut post = match post::Post::get_by_id(&**pg_client, &uuid::Uuid::from(post_id)).await {
-            Ok(post) => post,
-            Err(err) => {
-                log::debug!("unable to update post: {:?}", err);
-                return Ok(HttpResponse::InternalServerError().json("unable to update post"));
-            }
-        };
-
-        if user_id != post.get_user_id() {
-            log::debug!("unable to update post: user is not owner");
-            return Ok(HttpResponse::InternalServerError()
-                .json("unable to update post: user is not owner"));
+    let mut post = match post::Post::get_by_id(&**pg_client, &uuid::Uuid::from(post_id)).await {
+        Ok(post) => post,
+        Err(err) => {
+            log::debug!("unable to update post: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to update post"));
         }
+    };
 
-        post.set_content(&post_data.text);
+    if !user.is_owner_of(&post.get_user_id()) {
+        log::debug!("unable to update post: user is not owner");
+        return Ok(HttpResponse::Forbidden().json("unable to update post: user is not owner"));
+    }
 
-        match post::Post::update(&**pg_client, &post).await {
-            Ok(_res) => Ok(HttpResponse::Ok().json("ok")),
-            Err(err) => {
-                log::debug!("unable to update post: {:?}", err);
-                return Ok(HttpResponse::InternalServerError().json("unable to update post"));
-            }
+    post.set_content(&post_data.text);
+
+    match post::Post::update(&**pg_client, &post).await {
+        Ok(_res) => Ok(HttpResponse::Ok().json("ok")),
+        Err(err) => {
+            log::debug!("unable to update post: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to update post"));
         }
-    } else {
-        log::debug!("unable to update post: unauthorized");
-        return Ok(HttpResponse::InternalServerError().json("unable to update post"));
     }
 }
 
 async fn post_delete(
     pg_pool: web::Data<&'static PostgresPool>,
     path: web::Path<String>,
-    auth: BearerAuth,
+    user: auth::AuthenticatedUser,
 ) -> Result<HttpResponse, Error> {
     let post_id = match uuid::Uuid::from_str(&path.parse::<String>().unwrap()) {
         Ok(val) => val,
@@ -709,32 +2012,25 @@ async fn post_delete(
         }
     };
 
-    if let Ok(Some(session)) = session::Session::get_by_id(auth.token()).await {
-        let user_id = session.get_user_id();
-        let post = match post::Post::get_by_id(&**pg_client, &post_id).await {
-            Ok(post) => post,
-            Err(err) => {
-                log::debug!("unable to get post: {:?}", err);
-                return Ok(HttpResponse::InternalServerError().json("unable to get post"));
-            }
-        };
-
-        if user_id != post.get_user_id() {
-            log::debug!("unable to delete post: user is not owner");
-            return Ok(HttpResponse::InternalServerError()
-                .json("unable to delete post: user is not owner"));
+    let post = match post::Post::get_by_id(&**pg_client, &post_id).await {
+        Ok(post) => post,
+        Err(err) => {
+            log::debug!("unable to get post: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to get post"));
         }
+    };
 
-        match post::Post::delete(&**pg_client, &post).await {
-            Ok(_res) => Ok(HttpResponse::Ok().json("ok")),
-            Err(err) => {
-                log::debug!("unable to delete post: {:?}", err);
-                return Ok(HttpResponse::InternalServerError().json("unable to delete post"));
-            }
+    if !user.is_owner_of(&post.get_user_id()) {
+        log::debug!("unable to delete post: user is not owner");
+        return Ok(HttpResponse::Forbidden().json("unable to delete post: user is not owner"));
+    }
+
+    match post::Post::delete(&**pg_client, &post).await {
+        Ok(_res) => Ok(HttpResponse::Ok().json("ok")),
+        Err(err) => {
+            log::debug!("unable to delete post: {:?}", err);
+            return Ok(HttpResponse::InternalServerError().json("unable to delete post"));
         }
-    } else {
-        log::debug!("unable to delete post: unauthorized");
-        return Ok(HttpResponse::InternalServerError().json("unable to delete post"));
     }
 }
 
@@ -751,24 +2047,26 @@ async fn dialog_send(
     req: HttpRequest,
     path: web::Path<String>,
     mut payload: web::Payload,
-    auth: BearerAuth,
+    user: auth::AuthenticatedUser,
 ) -> HttpResponse {
     log_request(&req);
 
-    let message_sender_user_id: uuid::Uuid;
-    if let Ok(Some(session)) = session::Session::get_by_id(auth.token()).await {
-        message_sender_user_id = session.get_user_id();
-    } else {
-        log::debug!("Unable to send dialog message: unauthorized");
-        return HttpResponse::InternalServerError()
-            .json("Unable to send dialog message: unauthorized");
-    }
+    let message_sender_user_id = user.get_user_id();
 
     let message_receiver_user_id = match uuid::Uuid::parse_str(&path) {
         Ok(user_id2) => user_id2,
         Err(_err) => return HttpResponse::InternalServerError().json("User id is not specified"),
     };
 
+    match user_block::UserBlock::is_blocked_between(&message_sender_user_id, &message_receiver_user_id).await {
+        Ok(false) => (),
+        Ok(true) => return HttpResponse::Forbidden().json("Unable to send message: user is blocked"),
+        Err(err) => {
+            log::debug!("Unable to check user blocks: {:?}", err);
+            return HttpResponse::InternalServerError().json("Unable to check user blocks");
+        }
+    }
+
     let mut body = web::BytesMut::new();
     while let Some(chunk) = payload.next().await {
         let chunk = chunk.unwrap();
@@ -814,6 +2112,7 @@ async fn dialog_send(
         .build()
         .unwrap()
         .post(dialog_service_url + "/send")
+        .headers(access_token::get_headers(user.get_session()))
         .header("x-request-id", request_id_str)
         .json(&dialogs_body)
         .send()
@@ -842,17 +2141,10 @@ async fn dialog_send(
         .body(res_text)
 }
 
-async fn dialog_count(req: HttpRequest, auth: BearerAuth) -> HttpResponse {
+async fn dialog_count(req: HttpRequest, user: auth::AuthenticatedUser) -> HttpResponse {
     log_request(&req);
 
-    let dialog_id: uuid::Uuid =
-        if let Ok(Some(session)) = session::Session::get_by_id(auth.token()).await {
-            session.get_user_id()
-        } else {
-            log::debug!("Unable to send dialog message: unauthorized");
-            return HttpResponse::InternalServerError()
-                .json("Unable to send dialog message: unauthorized");
-        };
+    let dialog_id = user.get_user_id();
 
     let dialog_service_url = std::env::var("UNREAD_SERVICE_URL")
         .unwrap_or_else(|_| String::from("unread:8001"))
@@ -875,6 +2167,7 @@ async fn dialog_count(req: HttpRequest, auth: BearerAuth) -> HttpResponse {
         .build()
         .unwrap()
         .post(dialog_service_url + "/count")
+        .headers(access_token::get_headers(user.get_session()))
         .header("x-request-id", x_request_id)
         .json(&dialogs_body)
         .send()
@@ -914,18 +2207,11 @@ async fn dialog_list(
     req: HttpRequest,
     path: web::Path<String>,
     search: web::Query<DialogListRequestQuery>,
-    auth: BearerAuth,
+    user: auth::AuthenticatedUser,
 ) -> HttpResponse {
     log_request(&req);
 
-    let user_id1: uuid::Uuid =
-        if let Ok(Some(session)) = session::Session::get_by_id(auth.token()).await {
-            session.get_user_id()
-        } else {
-            log::debug!("Unable to send dialog message: unauthorized");
-            return HttpResponse::InternalServerError()
-                .json("Unable to send dialog message: unauthorized");
-        };
+    let user_id1 = user.get_user_id();
 
     let user_id2 = match uuid::Uuid::parse_str(&path) {
         Ok(uid) => uid,
@@ -955,6 +2241,7 @@ async fn dialog_list(
             + "/list",
     ];
 
+    let session = user.get_session();
     let bodies = stream::iter(urls)
         .map(|url| {
             let client = &client;
@@ -962,6 +2249,7 @@ async fn dialog_list(
             async move {
                 let res = client
                     .post(url + "/list")
+                    .headers(access_token::get_headers(session))
                     .header("x-request-id", x_request_id)
                     .json(&dialogs_body)
                     .send()
@@ -1007,17 +2295,10 @@ async fn dialog_list(
         .json(messages)
 }
 
-async fn dialog_get_unread(req: HttpRequest, auth: BearerAuth) -> HttpResponse {
+async fn dialog_get_unread(req: HttpRequest, user: auth::AuthenticatedUser) -> HttpResponse {
     log_request(&req);
 
-    let user_id1: uuid::Uuid =
-        if let Ok(Some(session)) = session::Session::get_by_id(auth.token()).await {
-            session.get_user_id()
-        } else {
-            log::debug!("Unable to send dialog message: unauthorized");
-            return HttpResponse::InternalServerError()
-                .json("Unable to send dialog message: unauthorized");
-        };
+    let user_id1 = user.get_user_id();
 
     let dialog_service_url =
         std::env::var("UNREAD_SERVICE_URL").unwrap_or_else(|_| String::from("unread:8001"));
@@ -1039,6 +2320,7 @@ async fn dialog_get_unread(req: HttpRequest, auth: BearerAuth) -> HttpResponse {
         .build()
         .unwrap()
         .post(dialog_service_url + "/get_unread")
+        .headers(access_token::get_headers(user.get_session()))
         .header("x-request-id", x_request_id)
         .json(&dialogs_body)
         .send()
@@ -1072,18 +2354,11 @@ async fn dialog_mark_as_read(
     req: HttpRequest,
     path: web::Path<String>,
     mut payload: web::Payload,
-    auth: BearerAuth,
+    user: auth::AuthenticatedUser,
 ) -> HttpResponse {
     log_request(&req);
 
-    let user_id1: uuid::Uuid =
-        if let Ok(Some(session)) = session::Session::get_by_id(auth.token()).await {
-            session.get_user_id()
-        } else {
-            log::debug!("Unable to send dialog message: unauthorized");
-            return HttpResponse::InternalServerError()
-                .json("Unable to send dialog message: unauthorized");
-        };
+    let user_id1 = user.get_user_id();
 
     let mut body = web::BytesMut::new();
     while let Some(chunk) = payload.next().await {
@@ -1128,6 +2403,7 @@ async fn dialog_mark_as_read(
         .build()
         .unwrap()
         .post(dialog_service_url + "/messages/get")
+        .headers(access_token::get_headers(user.get_session()))
         .header("x-request-id", x_request_id)
         .json(&dialogs_body)
         .send()
@@ -1164,6 +2440,7 @@ async fn dialog_mark_as_read(
         .build()
         .unwrap()
         .post(dialog_service_url + "/send_messages_vec")
+        .headers(access_token::get_headers(user.get_session()))
         .header("x-request-id", x_request_id)
         .json(&dialogs_body)
         .send()
@@ -1195,6 +2472,7 @@ async fn dialog_mark_as_read(
         .build()
         .unwrap()
         .post(dialog_service_url + "/messages/remove")
+        .headers(access_token::get_headers(user.get_session()))
         .header("x-request-id", x_request_id)
         .json(&dialogs_body)
         .send()
@@ -1216,41 +2494,15 @@ async fn main() -> std::io::Result<()> {
     rabbitmq::create_pub_sub().await;
     post::create_pub_sub().await;
 
-    session::init_storage(Box::new(
-        // postgres_session_storage::PostgresSessionStorage::new(
-        //     postgres::get_master_pool_ref(),
-        //     postgres::get_replica_pool_ref()
-        // )
-        TarantoolSessionStorage::new(tarantool::TarantoolClientManager::new().await),
-    ))
-    .await;
-
-    friend::init_storage(Box::new(
-        // PostgresFriendStorage::new(
-        //     postgres::get_master_pool_ref(),
-        //     postgres::get_replica_pool_ref()
-        // )
-        TarantoolFriendStorage::new(tarantool::TarantoolClientManager::new().await),
-    ))
-    .await;
+    storage::init_session_storage().await?;
+    storage::init_friend_storage().await?;
 
-    // let grpc_address = std::env::var("GRPC_SERVER_ADDRESS")
-    //     .unwrap_or_else(|_| "127.0.0.1:9000".into())
-    //     .parse::<std::net::SocketAddr>()
-    //     .unwrap_or_else(|_| std::net::SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1)), 9000));
-
-    // let grpc_server = tonic::transport::Server::builder()
-    //     .tls_config(tonic::transport::server::ServerTlsConfig::new().identity(
-    //         tonic::transport::Identity::from_pem(&std::fs::read_to_string("cert.pem")?,
-    //         &std::fs::read_to_string("key.pem")?))
-    //     )
-    //     .unwrap()
-    //     .add_service(
-    //         UserSearchServer::new(UserSearchService { pg_pool: postgres::get_replica_pool_ref() })
-    //             .send_compressed(CompressionEncoding::Gzip)
-    //             .accept_compressed(CompressionEncoding::Gzip)
-    //     )
-    //     .serve(grpc_address);
+    match std::env::var("PASSWORD_RESET_SINK").unwrap_or_else(|_| String::from("log")).as_str() {
+        "file" => password_reset::init_sink(Box::new(FilePasswordResetSink::new(
+            std::env::var("PASSWORD_RESET_SINK_FILE").unwrap_or_else(|_| String::from("password_reset_tokens.log")),
+        ))).await,
+        _ => password_reset::init_sink(Box::new(LogPasswordResetSink::new())).await,
+    };
 
     // postgres::migrate_down(postgres::get_master_pool_ref()).await;
     postgres::migrate_up(
@@ -1259,6 +2511,8 @@ async fn main() -> std::io::Result<()> {
     )
     .await;
 
+    account_deletion::spawn();
+
     let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
     builder
         .set_private_key_file("key.pem", SslFiletype::PEM)
@@ -1276,6 +2530,11 @@ async fn main() -> std::io::Result<()> {
                     .app_data(web::Data::new(postgres::get_replica_pool_ref()))
                     .route(web::get().to(list_users)),
             )
+            .service(
+                web::resource("/user/batch")
+                    .app_data(web::Data::new(postgres::get_replica_pool_ref()))
+                    .route(web::post().to(get_users_batch)),
+            )
             .service(
                 web::resource("/user/get/{id}")
                     .app_data(web::Data::new(postgres::get_replica_pool_ref()))
@@ -1294,17 +2553,154 @@ async fn main() -> std::io::Result<()> {
             .service(
                 web::resource("/login")
                     .app_data(web::Data::new(postgres::get_master_pool_ref()))
+                    .app_data(web::Data::new(redis::get_pool_ref()))
                     .route(web::post().to(login)),
             )
+            .service(
+                web::resource("/logout")
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("session"),
+                    )
+                    .route(web::post().to(logout)),
+            )
+            .service(
+                web::resource("/logout/all")
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("session"),
+                    )
+                    .route(web::post().to(logout_all)),
+            )
+            .service(
+                web::resource("/user/sessions")
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("session"),
+                    )
+                    .route(web::get().to(user_sessions)),
+            )
+            .service(
+                web::resource("/user/sessions/{id}")
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("session"),
+                    )
+                    .route(web::delete().to(user_session_delete)),
+            )
+            .service(
+                web::resource("/user/me")
+                    .app_data(web::Data::new(postgres::get_master_pool_ref()))
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("user"),
+                    )
+                    .route(web::put().to(user_update))
+                    .route(web::patch().to(user_update))
+                    .route(web::delete().to(user_delete)),
+            )
+            .service(
+                web::resource("/user/me/export")
+                    .app_data(web::Data::new(postgres::get_master_pool_ref()))
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("user"),
+                    )
+                    .route(web::get().to(user_export)),
+            )
+            .service(
+                web::resource("/user/password")
+                    .app_data(web::Data::new(postgres::get_master_pool_ref()))
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("user"),
+                    )
+                    .route(web::post().to(user_password_change)),
+            )
+            .service(
+                web::resource("/user/password/reset")
+                    .app_data(web::Data::new(postgres::get_master_pool_ref()))
+                    .route(web::post().to(user_password_reset_request)),
+            )
+            .service(
+                web::resource("/user/password/reset/confirm")
+                    .app_data(web::Data::new(postgres::get_master_pool_ref()))
+                    .route(web::post().to(user_password_reset_confirm)),
+            )
             .service(
                 web::resource("/friend/set/{user_id}")
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("friend"),
+                    )
+                    .app_data(web::Data::new(postgres::get_replica_pool_ref()))
+                    .route(web::put().to(friend_request_send)),
+            )
+            .service(
+                web::resource("/friend/request/{user_id}")
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("friend"),
+                    )
+                    .app_data(web::Data::new(postgres::get_replica_pool_ref()))
+                    .route(web::post().to(friend_request_send)),
+            )
+            .service(
+                web::resource("/friend/requests/incoming")
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("friend"),
+                    )
+                    .route(web::get().to(friend_requests_incoming)),
+            )
+            .service(
+                web::resource("/friend/requests/outgoing")
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("friend"),
+                    )
+                    .route(web::get().to(friend_requests_outgoing)),
+            )
+            .service(
+                web::resource("/friend/requests/{request_id}/accept")
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("friend"),
+                    )
+                    .app_data(web::Data::new(redis::get_pool_ref()))
+                    .route(web::post().to(friend_request_accept)),
+            )
+            .service(
+                web::resource("/friend/requests/{request_id}/decline")
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("friend"),
+                    )
+                    .app_data(web::Data::new(redis::get_pool_ref()))
+                    .route(web::post().to(friend_request_decline)),
+            )
+            .service(
+                web::resource("/friend/requests/{request_id}/cancel")
                     .app_data(
                         bearer::Config::default()
                             .realm("Restricted area")
                             .scope("friend"),
                     )
                     .app_data(web::Data::new(redis::get_pool_ref()))
-                    .route(web::put().to(friend_set)),
+                    .route(web::post().to(friend_request_cancel)),
             )
             .service(
                 web::resource("/friend/search")
@@ -1316,6 +2712,48 @@ async fn main() -> std::io::Result<()> {
                     .app_data(web::Data::new(postgres::get_replica_pool_ref()))
                     .route(web::get().to(friend_search)),
             )
+            .service(
+                web::resource("/friend/suggestions")
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("friend"),
+                    )
+                    .app_data(web::Data::new(postgres::get_replica_pool_ref()))
+                    .app_data(web::Data::new(redis::get_pool_ref()))
+                    .route(web::get().to(friend_suggestions)),
+            )
+            .service(
+                web::resource("/friend/{user_id}/mutual")
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("friend"),
+                    )
+                    .app_data(web::Data::new(postgres::get_replica_pool_ref()))
+                    .route(web::get().to(friend_mutual)),
+            )
+            .service(
+                web::resource("/friend/block/{user_id}")
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("friend"),
+                    )
+                    .app_data(web::Data::new(postgres::get_replica_pool_ref()))
+                    .app_data(web::Data::new(redis::get_pool_ref()))
+                    .route(web::post().to(user_block_create))
+                    .route(web::delete().to(user_block_delete)),
+            )
+            .service(
+                web::resource("/friend/blocks")
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("friend"),
+                    )
+                    .route(web::get().to(user_block_list)),
+            )
             .service(
                 web::resource("/friend/delete/{user_id}")
                     .app_data(
@@ -1421,7 +2859,16 @@ async fn main() -> std::io::Result<()> {
     .bind_openssl(&http_address, builder)?
     .run();
 
-    let _ = future::try_join(tokio::spawn(http_server), tokio::spawn(websocket::serve())).await?;
+    future::try_join3(
+        flatten(tokio::spawn(http_server)),
+        flatten(tokio::spawn(websocket::serve())),
+        flatten(tokio::spawn(serve_grpc())),
+    ).await?;
 
     Ok(())
 }
+
+// A server that fails to start stops the whole process instead of being dropped with its task
+async fn flatten<T>(handle: tokio::task::JoinHandle<std::io::Result<T>>) -> std::io::Result<T> {
+    handle.await?
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use futures::io;
+use tokio::sync::RwLock;
+use tonic::async_trait;
+use uuid::Uuid;
+
+use crate::friend::{Friend, FriendCounts};
+use crate::friend_request::{FriendRequest, FriendRequestStatus};
+use crate::friend_storage::FriendStorage;
+use crate::user_block::UserBlock;
+
+pub struct MemoryFriendStorage {
+    friends: RwLock<HashMap<Uuid, Friend>>,
+    requests: RwLock<HashMap<Uuid, FriendRequest>>,
+    blocks: RwLock<HashMap<Uuid, UserBlock>>,
+}
+
+impl MemoryFriendStorage {
+    pub fn new() -> Self {
+        Self {
+            friends: RwLock::new(HashMap::new()),
+            requests: RwLock::new(HashMap::new()),
+            blocks: RwLock::new(HashMap::new()),
+        }
+    }
+
+    async fn get_pending_requests_by(&self, predicate: impl Fn(&FriendRequest) -> bool) -> Vec<FriendRequest> {
+        let mut requests: Vec<FriendRequest> = self.requests.read().await
+            .values()
+            .filter(|request| request.get_status() == FriendRequestStatus::Pending && predicate(request))
+            .cloned()
+            .collect();
+        requests.sort_by(|left, right| right.get_time_created().cmp(left.get_time_created()));
+        requests
+    }
+}
+
+#[async_trait]
+impl FriendStorage for MemoryFriendStorage {
+    async fn get_by_id(&self, id: &Uuid) -> Result<Option<Friend>, io::Error> {
+        Ok(self.friends.read().await.get(id).cloned())
+    }
+
+    async fn get_by_user_id(&self, user_id: &Uuid) -> Result<Vec<Friend>, io::Error> {
+        Ok(self.friends.read().await
+            .values()
+            .filter(|friend| friend.get_user_id() == *user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_by_friend_id(&self, friend_id: &Uuid) -> Result<Vec<Friend>, io::Error> {
+        Ok(self.friends.read().await
+            .values()
+            .filter(|friend| friend.get_friend_id() == *friend_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_by_user_ids(&self, user_ids: &[Uuid]) -> Result<Vec<Friend>, io::Error> {
+        Ok(self.friends.read().await
+            .values()
+            .filter(|friend| user_ids.contains(&friend.get_user_id()))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_by_friend_ids(&self, friend_ids: &[Uuid]) -> Result<Vec<Friend>, io::Error> {
+        Ok(self.friends.read().await
+            .values()
+            .filter(|friend| friend_ids.contains(&friend.get_friend_id()))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_by_user_id_and_friend_id(&self, user_id: &Uuid, friend_id: &Uuid) -> Result<Option<Friend>, io::Error> {
+        Ok(self.friends.read().await
+            .values()
+            .find(|friend| friend.get_user_id() == *user_id && friend.get_friend_id() == *friend_id)
+            .cloned())
+    }
+
+    async fn get_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Friend>, io::Error> {
+        let friends = self.friends.read().await;
+        let mut ids: Vec<&Uuid> = friends.keys().filter(|id| after_id.map_or(true, |after_id| *id > after_id)).collect();
+        ids.sort();
+
+        Ok(ids.into_iter().take(limit).filter_map(|id| friends.get(id).cloned()).collect())
+    }
+
+    async fn create(&self, friend: &Friend) -> Result<Uuid, io::Error> {
+        self.friends.write().await.insert(friend.get_id(), friend.clone());
+
+        Ok(friend.get_id())
+    }
+
+    async fn delete(&self, friend: &Friend) -> Result<bool, io::Error> {
+        self.friends.write().await.retain(|_, stored| {
+            stored.get_user_id() != friend.get_user_id() || stored.get_friend_id() != friend.get_friend_id()
+        });
+
+        Ok(true)
+    }
+
+    async fn is_persistant(&self, friend: &Friend) -> Result<bool, io::Error> {
+        Ok(self.get_by_user_id_and_friend_id(&friend.get_user_id(), &friend.get_friend_id()).await?.is_some())
+    }
+
+    // Counting in place is as cheap as keeping counters for a map in memory
+    async fn get_counts(&self, user_id: &Uuid) -> Result<FriendCounts, io::Error> {
+        let friends = self.friends.read().await;
+        let followers_count = friends.values().filter(|friend| friend.get_friend_id() == *user_id).count();
+        let following_count = friends.values().filter(|friend| friend.get_user_id() == *user_id).count();
+
+        Ok(FriendCounts::new(followers_count as u64, following_count as u64))
+    }
+
+    async fn get_request_by_id(&self, id: &Uuid) -> Result<Option<FriendRequest>, io::Error> {
+        Ok(self.requests.read().await.get(id).cloned())
+    }
+
+    async fn get_pending_request(&self, from_user_id: &Uuid, to_user_id: &Uuid) -> Result<Option<FriendRequest>, io::Error> {
+        Ok(self.get_pending_requests_by(|request| {
+            request.get_from_user_id() == *from_user_id && request.get_to_user_id() == *to_user_id
+        }).await.into_iter().next())
+    }
+
+    async fn get_pending_requests_by_to_user_id(&self, to_user_id: &Uuid) -> Result<Vec<FriendRequest>, io::Error> {
+        Ok(self.get_pending_requests_by(|request| request.get_to_user_id() == *to_user_id).await)
+    }
+
+    async fn get_pending_requests_by_from_user_id(&self, from_user_id: &Uuid) -> Result<Vec<FriendRequest>, io::Error> {
+        Ok(self.get_pending_requests_by(|request| request.get_from_user_id() == *from_user_id).await)
+    }
+
+    async fn create_request(&self, request: &FriendRequest) -> Result<Uuid, io::Error> {
+        self.requests.write().await.insert(request.get_id(), request.clone());
+
+        Ok(request.get_id())
+    }
+
+    async fn update_request_status(&self, request: &FriendRequest) -> Result<bool, io::Error> {
+        let mut requests = self.requests.write().await;
+        match requests.get_mut(&request.get_id()) {
+            Some(stored) if stored.get_status() == FriendRequestStatus::Pending => {
+                *stored = request.clone();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn accept_request(&self, request: &FriendRequest, friends: &[Friend]) -> Result<bool, io::Error> {
+        // Both locks are held so that the request and the friend records change together
+        let mut requests = self.requests.write().await;
+        let mut stored_friends = self.friends.write().await;
+        match requests.get_mut(&request.get_id()) {
+            Some(stored) if stored.get_status() == FriendRequestStatus::Pending => *stored = request.clone(),
+            _ => return Ok(false),
+        }
+        for friend in friends.iter() {
+            let is_persistant = stored_friends.values().any(|stored| {
+                stored.get_user_id() == friend.get_user_id() && stored.get_friend_id() == friend.get_friend_id()
+            });
+            if !is_persistant {
+                stored_friends.insert(friend.get_id(), friend.clone());
+            }
+        }
+        Ok(true)
+    }
+
+    async fn delete_requests_by_user_id(&self, user_id: &Uuid) -> Result<u64, io::Error> {
+        let mut requests = self.requests.write().await;
+        let count = requests.len();
+        requests.retain(|_, request| request.get_from_user_id() != *user_id && request.get_to_user_id() != *user_id);
+
+        Ok((count - requests.len()) as u64)
+    }
+
+    async fn get_block(&self, user_id: &Uuid, blocked_user_id: &Uuid) -> Result<Option<UserBlock>, io::Error> {
+        Ok(self.blocks.read().await.values()
+            .find(|block| block.get_user_id() == *user_id && block.get_blocked_user_id() == *blocked_user_id)
+            .cloned())
+    }
+
+    async fn get_blocks_by_user_id(&self, user_id: &Uuid) -> Result<Vec<UserBlock>, io::Error> {
+        Ok(self.blocks.read().await.values().filter(|block| block.get_user_id() == *user_id).cloned().collect())
+    }
+
+    async fn get_blocks_by_blocked_user_id(&self, blocked_user_id: &Uuid) -> Result<Vec<UserBlock>, io::Error> {
+        Ok(self.blocks.read().await.values().filter(|block| block.get_blocked_user_id() == *blocked_user_id).cloned().collect())
+    }
+
+    async fn create_block(&self, block: &UserBlock) -> Result<Uuid, io::Error> {
+        let mut blocks = self.blocks.write().await;
+        let existing = blocks.values().find(|stored| {
+            stored.get_user_id() == block.get_user_id() && stored.get_blocked_user_id() == block.get_blocked_user_id()
+        });
+        if let Some(existing) = existing {
+            return Ok(existing.get_id());
+        }
+        blocks.insert(block.get_id(), block.clone());
+
+        Ok(block.get_id())
+    }
+
+    async fn delete_block(&self, user_id: &Uuid, blocked_user_id: &Uuid) -> Result<bool, io::Error> {
+        let mut blocks = self.blocks.write().await;
+        let count = blocks.len();
+        blocks.retain(|_, block| block.get_user_id() != *user_id || block.get_blocked_user_id() != *blocked_user_id);
+
+        Ok(count != blocks.len())
+    }
+
+    async fn delete_blocks_by_user_id(&self, user_id: &Uuid) -> Result<u64, io::Error> {
+        let mut blocks = self.blocks.write().await;
+        let count = blocks.len();
+        blocks.retain(|_, block| block.get_user_id() != *user_id && block.get_blocked_user_id() != *user_id);
+
+        Ok((count - blocks.len()) as u64)
+    }
+}
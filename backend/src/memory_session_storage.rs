@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use futures::io;
+use tokio::sync::RwLock;
+use tonic::async_trait;
+use uuid::Uuid;
+
+use crate::session::Session;
+use crate::session_storage::SessionStorage;
+
+pub struct MemorySessionStorage {
+    sessions: RwLock<HashMap<Uuid, Session>>,
+}
+
+impl MemorySessionStorage {
+    pub fn new() -> Self {
+        Self {
+            sessions: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl SessionStorage for MemorySessionStorage {
+    async fn get_by_id(&self, id: &str) -> Result<Option<Session>, io::Error> {
+        let id = match Uuid::parse_str(id) {
+            Ok(id) => id,
+            Err(_) => return Ok(None),
+        };
+
+        Ok(self.sessions.read().await.get(&id).cloned())
+    }
+
+    async fn get_by_user_id(&self, user_id: &Uuid) -> Result<Vec<Session>, io::Error> {
+        Ok(self.sessions.read().await
+            .values()
+            .filter(|session| session.get_user_id() == *user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Session>, io::Error> {
+        let sessions = self.sessions.read().await;
+        let mut ids: Vec<&Uuid> = sessions.keys().filter(|id| after_id.map_or(true, |after_id| *id > after_id)).collect();
+        ids.sort();
+
+        Ok(ids.into_iter().take(limit).filter_map(|id| sessions.get(id).cloned()).collect())
+    }
+
+    async fn create(&self, session: &Session) -> Result<Uuid, io::Error> {
+        self.sessions.write().await.insert(session.get_id(), session.clone());
+
+        Ok(session.get_id())
+    }
+
+    async fn touch(&self, id: &Uuid, time_updated: &chrono::NaiveDateTime) -> Result<bool, io::Error> {
+        match self.sessions.write().await.get_mut(id) {
+            Some(session) => {
+                session.set_time_updated(*time_updated);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<bool, io::Error> {
+        Ok(self.sessions.write().await.remove(id).is_some())
+    }
+
+    async fn delete_by_user_id(&self, user_id: &Uuid, except_id: Option<&Uuid>) -> Result<u64, io::Error> {
+        let mut sessions = self.sessions.write().await;
+        let count = sessions.len();
+        sessions.retain(|id, session| session.get_user_id() != *user_id || Some(id) == except_id);
+
+        Ok((count - sessions.len()) as u64)
+    }
+}
@@ -0,0 +1,88 @@
+use lazy_static::lazy_static;
+use rand::{distributions::Alphanumeric, Rng as _};
+use tokio::sync::OnceCell;
+use tokio_postgres::{Error as PostgresError, GenericClient};
+use uuid::Uuid;
+
+use crate::password_reset_sink::PasswordResetSink;
+use crate::user::User;
+
+const TOKEN_SECRET_LENGTH: usize = 32;
+
+static SINK: OnceCell<Box<dyn PasswordResetSink + Send + Sync>> = OnceCell::const_new();
+
+lazy_static! {
+    pub static ref PASSWORD_RESET_TOKEN_TTL_SECONDS: i64 = std::env::var("PASSWORD_RESET_TOKEN_TTL_SECONDS").unwrap_or_else(|_| "3600".to_string()).parse::<i64>().unwrap_or(3600);
+}
+
+pub async fn init_sink(sink: Box<dyn PasswordResetSink + Send + Sync>) {
+    if !SINK.initialized() {
+        SINK.get_or_init(|| async {sink}).await;
+    }
+}
+
+fn get_sink() -> &'static Box<dyn PasswordResetSink + Send + Sync> {
+    SINK.get().expect("Password reset sink must be initialized first")
+}
+
+pub struct PasswordResetToken {}
+
+impl PasswordResetToken {
+    // Token is handed out as "<id>.<secret>", only an Argon2 hash of the secret is stored
+    pub async fn issue<C: GenericClient>(client: &C, user: &User) -> Result<(), PostgresError> {
+        let id = Uuid::new_v4();
+        let secret: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_SECRET_LENGTH)
+            .map(char::from)
+            .collect();
+        let (token_hash, _salt) = User::encrypt_password(&secret);
+        let time_expires = chrono::Utc::now().naive_utc() + chrono::Duration::seconds(*PASSWORD_RESET_TOKEN_TTL_SECONDS);
+
+        let stmt = client.prepare(
+            "UPDATE password_reset_tokens SET time_used = NOW() WHERE user_id = $1 AND time_used IS NULL"
+        ).await?;
+        client.execute(&stmt, &[&user.id()]).await?;
+
+        let stmt = client.prepare(
+            "INSERT INTO password_reset_tokens (id, user_id, token_hash, time_expires) VALUES ($1, $2, $3, $4)"
+        ).await?;
+        client.execute(&stmt, &[&id, &user.id(), &token_hash, &time_expires]).await?;
+
+        let token = id.to_string() + "." + secret.as_str();
+        if let Err(err) = get_sink().deliver(user, token.as_str()).await {
+            log::error!("unable to deliver password reset token for user '{}': {:?}", user.id(), err);
+        }
+
+        Ok(())
+    }
+
+    pub async fn redeem<C: GenericClient>(client: &C, token: &str) -> Result<Option<Uuid>, PostgresError> {
+        let (id, secret) = match token.split_once('.') {
+            Some((id, secret)) => match Uuid::parse_str(id) {
+                Ok(id) => (id, secret.to_string()),
+                Err(_) => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+
+        let stmt = client.prepare(
+            "SELECT token_hash FROM password_reset_tokens WHERE id = $1 AND time_used IS NULL AND time_expires > NOW()"
+        ).await?;
+        let token_hash: String = match client.query_opt(&stmt, &[&id]).await? {
+            Some(row) => row.get(0),
+            None => return Ok(None),
+        };
+
+        if !User::check_password(&secret, &token_hash) {
+            return Ok(None);
+        }
+
+        let stmt = client.prepare(
+            "UPDATE password_reset_tokens SET time_used = NOW() WHERE id = $1 AND time_used IS NULL RETURNING user_id"
+        ).await?;
+        let row = client.query_opt(&stmt, &[&id]).await?;
+
+        Ok(row.map(|row| row.get(0)))
+    }
+}
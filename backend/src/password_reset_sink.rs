@@ -0,0 +1,9 @@
+use std::io::Error;
+use tonic::async_trait;
+
+use crate::user::User;
+
+#[async_trait]
+pub trait PasswordResetSink {
+    async fn deliver(&self, user: &User, token: &str) -> Result<(), Error>;
+}
\ No newline at end of file
@@ -1,5 +1,6 @@
 use std::{cmp, fmt};
 use std::error::Error;
+use std::str::FromStr;
 use amqprs::channel::{BasicAckArguments, BasicConsumeArguments, Channel};
 use amqprs::consumer::AsyncConsumer;
 use amqprs::{BasicProperties, Deliver};
@@ -10,9 +11,11 @@ use tonic::async_trait;
 use uuid::Uuid;
 use lazy_static::lazy_static;
 
-use crate::{friend, postgres, rabbitmq, redis, websocket};
+use crate::{friend, postgres, rabbitmq, redis, user_block, websocket};
 
 pub const FEED_LENGTH: i64 = 1000;
+pub const FEED_PAGE_DEFAULT_LIMIT: usize = 20;
+pub const FEED_PAGE_MAX_LIMIT: usize = 100;
 pub const FEED_CACHE_KEY_PREFIX: &str = "feed:";
 
 pub const FEED_QUEUE_NAME: &str = "feed.amqprs.post";
@@ -80,6 +83,49 @@ impl Error for PostDataError {
     }
 }
 
+// Position in a feed ordered by (time_updated, id) descending, in microseconds like Postgres timestamps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FeedCursor {
+    time_updated: i64,
+    id: Uuid,
+}
+
+impl FeedCursor {
+    fn of(post: &Post) -> FeedCursor {
+        // Posts cached straight from events keep nanoseconds, Postgres rounds them to microseconds
+        let nanos = post.time_updated.and_utc().timestamp_nanos_opt().unwrap_or_default();
+        FeedCursor { time_updated: (nanos + 500).div_euclid(1000), id: post.id }
+    }
+
+    fn get_time_updated(&self) -> chrono::NaiveDateTime {
+        chrono::DateTime::from_timestamp_micros(self.time_updated).unwrap_or_default().naive_utc()
+    }
+}
+
+impl fmt::Display for FeedCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}_{}", self.time_updated, self.id)
+    }
+}
+
+impl FromStr for FeedCursor {
+    type Err = PostDataError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (time_updated, id) = value.split_once('_').ok_or_else(|| PostDataError::new("feed cursor is incorrect"))?;
+        Ok(FeedCursor {
+            time_updated: time_updated.parse().map_err(|_| PostDataError::new("feed cursor is incorrect"))?,
+            id: Uuid::parse_str(id).map_err(|_| PostDataError::new("feed cursor is incorrect"))?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeedPage {
+    posts: Vec<Post>,
+    next_cursor: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 enum PostEvent {
     CREATED,
@@ -124,10 +170,19 @@ impl Post {
         self.user_id
     }
 
+    pub fn get_time_created(&self) -> chrono::NaiveDateTime {
+        self.time_created
+    }
+
+    pub fn get_time_updated(&self) -> chrono::NaiveDateTime {
+        self.time_updated
+    }
+
     pub async fn get_feed<C: GenericClient>(
         pg_client: &C,
         redis_connection: &mut Connection,
         user_id: &Uuid,
+        hidden_user_ids: &[Uuid],
         offset: &usize,
         limit: &usize
     ) -> Result<Vec<Post>, PostgresError> {
@@ -155,12 +210,12 @@ impl Post {
         if !has_cached_result {
             log::debug!("Cache miss for user_id: '{}'. Cache key: '{}'", user_id, cache_key);
             let stmt = pg_client.prepare(
-                "SELECT * FROM posts WHERE user_id IN (SELECT friend_id FROM friends WHERE user_id=$1) ORDER BY time_updated DESC LIMIT $2"
+                "SELECT * FROM posts WHERE user_id IN (SELECT friend_id FROM friends WHERE user_id=$1) AND NOT (user_id = ANY($3::uuid[])) ORDER BY time_updated DESC, id DESC LIMIT $2"
             ).await?;
 
             let rows = pg_client.query(
                 &stmt,
-                &[user_id, &self::FEED_LENGTH]
+                &[user_id, &self::FEED_LENGTH, &hidden_user_ids]
             ).await?;
 
             let mut posts: Vec<Post> = rows
@@ -199,13 +254,68 @@ impl Post {
             ).await
             .unwrap()
             .iter()
-            .map(|post| serde_json::from_str(post).unwrap())
+            .map(|post| serde_json::from_str::<Post>(post).unwrap())
+            // Blocking drops the cached feeds, this only covers a block racing with a cache refill
+            .filter(|post| !hidden_user_ids.contains(&post.user_id))
             .collect();
 
             Ok(posts)
         }
     }
 
+    // Pages through the cached head of the feed first and continues in Postgres past its end,
+    // so that neither new posts nor FEED_LENGTH shift or cut the pages
+    pub async fn get_feed_page<C: GenericClient>(
+        pg_client: &C,
+        redis_connection: &mut Connection,
+        user_id: &Uuid,
+        hidden_user_ids: &[Uuid],
+        cursor: Option<&FeedCursor>,
+        limit: usize,
+    ) -> Result<FeedPage, PostgresError> {
+        let limit = match limit {
+            0 => FEED_PAGE_DEFAULT_LIMIT,
+            value => value.min(FEED_PAGE_MAX_LIMIT),
+        };
+
+        let mut cached = Post::get_feed(pg_client, redis_connection, user_id, hidden_user_ids, &0, &(FEED_LENGTH as usize)).await?;
+        cached.sort_by_key(|post| cmp::Reverse(FeedCursor::of(post)));
+        let mut posts: Vec<Post> = cached.into_iter()
+            .filter(|post| !cursor.is_some_and(|cursor| FeedCursor::of(post) >= *cursor))
+            .take(limit + 1)
+            .collect();
+
+        if posts.len() <= limit {
+            let after = posts.last().map(FeedCursor::of).or(cursor.copied());
+            let stmt = pg_client.prepare(
+                "SELECT * FROM posts WHERE user_id IN (SELECT friend_id FROM friends WHERE user_id=$1) \
+                AND NOT (user_id = ANY($2::uuid[])) \
+                AND ($3::timestamp IS NULL OR (time_updated, id) < ($3, $4)) \
+                ORDER BY time_updated DESC, id DESC LIMIT $5"
+            ).await?;
+            let rows = pg_client.query(
+                &stmt,
+                &[
+                    user_id,
+                    &hidden_user_ids,
+                    &after.map(|after| after.get_time_updated()),
+                    &after.map(|after| after.id),
+                    &((limit + 1 - posts.len()) as i64),
+                ]
+            ).await?;
+            posts.extend(rows.iter().map(Post::from));
+        }
+
+        let next_cursor = if limit < posts.len() {
+            posts.truncate(limit);
+            posts.last().map(|post| FeedCursor::of(post).to_string())
+        } else {
+            None
+        };
+
+        Ok(FeedPage { posts, next_cursor })
+    }
+
     // pub async fn get_feed<C: GenericClient>(
     //     pg_client: &C,
     //     redis_connection: &mut Connection,
@@ -492,7 +602,11 @@ pub async fn publish_message(entity_id: &str, message: &str) {
     {
         let user_id = Uuid::parse_str(entity_id).unwrap();
         let users = friend::Friend::get_by_friend_id(&user_id).await.unwrap();
-        for user in users.iter() {
+        let hidden_user_ids = user_block::UserBlock::get_hidden_user_ids(&user_id).await.unwrap_or_else(|err| {
+            log::warn!("unable to get blocks of user '{}': {:?}", user_id, err);
+            Vec::new()
+        });
+        for user in users.iter().filter(|user| !hidden_user_ids.contains(&user.get_user_id())) {
             rabbitmq::publish_message(
                 rabbitmq::get_channel_ref().await.lock().await.first().unwrap(),
                 message,
@@ -502,3 +616,26 @@ pub async fn publish_message(entity_id: &str, message: &str) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feed_cursor_round_trip() {
+        let cursor = FeedCursor { time_updated: 1_700_000_000_123_456, id: Uuid::new_v4() };
+
+        assert_eq!(cursor.to_string().parse::<FeedCursor>().unwrap(), cursor);
+        assert_eq!(cursor.get_time_updated().and_utc().timestamp_micros(), 1_700_000_000_123_456);
+    }
+
+    #[test]
+    fn feed_cursor_rejects_malformed_values() {
+        let id = Uuid::new_v4();
+
+        let values = [String::new(), "1700000000123456".to_owned(), "abc_".to_owned() + id.to_string().as_str(), "1700000000123456_abc".to_owned()];
+        for value in values.iter() {
+            assert!(value.parse::<FeedCursor>().is_err(), "{}", value);
+        }
+    }
+}
@@ -7,7 +7,7 @@ use rand::seq::SliceRandom;
 
 use crate::friend;
 
-const SCRIPTS_UP: [(&str, &str); 10] = [(
+const SCRIPTS_UP: [(&str, &str); 21] = [(
     "0001_create-extension-uuid-ossp",
     include_str!("../migrations/0001_create-extension-uuid-ossp_up.sql"),
 ),(
@@ -37,6 +37,39 @@ const SCRIPTS_UP: [(&str, &str); 10] = [(
 ),(
     "0001_create_posts_up",
     include_str!("../migrations/0001_create_posts_up.sql"),
+),(
+    "0001_create_index-sessions-user_id_up",
+    include_str!("../migrations/0001_create_index-sessions-user_id_up.sql"),
+),(
+    "0001_alter-users-add-login_up",
+    include_str!("../migrations/0001_alter-users-add-login_up.sql"),
+),(
+    "0001_create_password-reset-tokens_up",
+    include_str!("../migrations/0001_create_password-reset-tokens_up.sql"),
+),(
+    "0001_create_index-sessions-id_up",
+    include_str!("../migrations/0001_create_index-sessions-id_up.sql"),
+),(
+    "0001_create_index-friends-id_up",
+    include_str!("../migrations/0001_create_index-friends-id_up.sql"),
+),(
+    "0001_create_index-users-city-id_up",
+    include_str!("../migrations/0001_create_index-users-city-id_up.sql"),
+),(
+    "0001_create_user-deletions_up",
+    include_str!("../migrations/0001_create_user-deletions_up.sql"),
+),(
+    "0001_create_user-exports_up",
+    include_str!("../migrations/0001_create_user-exports_up.sql"),
+),(
+    "0001_create_friend-requests_up",
+    include_str!("../migrations/0001_create_friend-requests_up.sql"),
+),(
+    "0001_create_friend-counts_up",
+    include_str!("../migrations/0001_create_friend-counts_up.sql"),
+),(
+    "0001_create_user-blocks_up",
+    include_str!("../migrations/0001_create_user-blocks_up.sql"),
 )];
 
 const SCRIPTS_DOWN: [(&str, &str); 2] = [(
@@ -1,10 +1,12 @@
 use deadpool_postgres::Pool;
 use futures::io;
-use tokio_postgres::Row;
+use tokio_postgres::{GenericClient, Row};
 use tonic::async_trait;
 use uuid::Uuid;
 
-use crate::{friend::Friend, friend_storage::FriendStorage};
+use crate::{friend::{Friend, FriendCounts}, friend_request::FriendRequest, friend_storage::FriendStorage, user_block::UserBlock};
+
+const FRIEND_REQUEST_COLUMNS: &str = "id, from_user_id, to_user_id, status, time_created, time_updated";
 
 impl From<Row> for Friend {
     fn from(row: Row) -> Friend {
@@ -12,6 +14,30 @@ impl From<Row> for Friend {
     }
 }
 
+fn user_block_from_row(row: Row) -> UserBlock {
+    UserBlock::restore(row.get(0), row.get(1), row.get(2), row.get(3))
+}
+
+fn friend_request_from_row(row: Row) -> Result<FriendRequest, io::Error> {
+    let status: String = row.get(3);
+    Ok(FriendRequest::restore(row.get(0), row.get(1), row.get(2), status.parse()?, row.get(4), row.get(5)))
+}
+
+// Applies a change of friend records between two users to friend_counts, delta is negative on delete
+async fn change_counts<C: GenericClient>(client: &C, user_id: &Uuid, friend_id: &Uuid, delta: i64) -> Result<(), io::Error> {
+    client.execute(
+        "INSERT INTO friend_counts (user_id, following_count) VALUES ($1, $2) \
+        ON CONFLICT (user_id) DO UPDATE SET following_count = friend_counts.following_count + $2",
+        &[user_id, &delta]
+    ).await.map_err(io::Error::other)?;
+    client.execute(
+        "INSERT INTO friend_counts (user_id, followers_count) VALUES ($1, $2) \
+        ON CONFLICT (user_id) DO UPDATE SET followers_count = friend_counts.followers_count + $2",
+        &[friend_id, &delta]
+    ).await.map_err(io::Error::other)?;
+    Ok(())
+}
+
 pub struct PostgresFriendStorage {
     master_pool: &'static Pool,
     replica_pool: &'static Pool,
@@ -71,6 +97,26 @@ impl FriendStorage for PostgresFriendStorage {
         Ok(friends)
     }
 
+    async fn get_by_user_ids(&self, user_ids: &[Uuid]) -> Result<Vec<Friend>, io::Error> {
+        let client = self.replica_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare("SELECT * FROM friends WHERE user_id = ANY($1::uuid[])").await.map_err(io::Error::other)?;
+
+        let rows = client.query(&stmt, &[&user_ids]).await.map_err(io::Error::other)?;
+
+        Ok(rows.into_iter().map(Friend::from).collect())
+    }
+
+    async fn get_by_friend_ids(&self, friend_ids: &[Uuid]) -> Result<Vec<Friend>, io::Error> {
+        let client = self.replica_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare("SELECT * FROM friends WHERE friend_id = ANY($1::uuid[])").await.map_err(io::Error::other)?;
+
+        let rows = client.query(&stmt, &[&friend_ids]).await.map_err(io::Error::other)?;
+
+        Ok(rows.into_iter().map(Friend::from).collect())
+    }
+
     async fn get_by_user_id_and_friend_id(&self, user_id: &Uuid, friend_id: &Uuid) -> Result<Option<Friend>, io::Error> {
         let client = self.replica_pool.get().await.unwrap();
 
@@ -83,34 +129,46 @@ impl FriendStorage for PostgresFriendStorage {
         }
     }
 
-    async fn create(&self, friend: &Friend) -> Result<Uuid, io::Error> {
-        let client = self.master_pool.get().await.unwrap();
+    async fn get_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Friend>, io::Error> {
+        let client = self.replica_pool.get().await.map_err(io::Error::other)?;
 
         let stmt = client.prepare(
-            "INSERT INTO friends (id, user_id, friend_id) VALUES ($1, $2, $3)"
-        ).await.unwrap();
+            "SELECT id, user_id, friend_id FROM friends WHERE $1::uuid IS NULL OR id > $1 ORDER BY id LIMIT $2"
+        ).await.map_err(io::Error::other)?;
 
-        client.execute(
-            &stmt,
+        let rows = client.query(&stmt, &[&after_id, &(limit as i64)]).await.map_err(io::Error::other)?;
+
+        Ok(rows.into_iter().map(Friend::from).collect())
+    }
+
+    async fn create(&self, friend: &Friend) -> Result<Uuid, io::Error> {
+        let mut client = self.master_pool.get().await.map_err(io::Error::other)?;
+        let transaction = client.transaction().await.map_err(io::Error::other)?;
+
+        transaction.execute(
+            "INSERT INTO friends (id, user_id, friend_id) VALUES ($1, $2, $3)",
             &[&friend.get_id(), &friend.get_user_id(), &friend.get_friend_id()]
-        ).await.unwrap();
+        ).await.map_err(io::Error::other)?;
+        change_counts(&*transaction, &friend.get_user_id(), &friend.get_friend_id(), 1).await?;
 
+        transaction.commit().await.map_err(io::Error::other)?;
         Ok(friend.get_id())
     }
 
     async fn delete(&self, friend: &Friend) -> Result<bool, io::Error> {
-        let client = self.master_pool.get().await.unwrap();
-
-        let stmt = client.prepare(
-            "DELETE FROM friends WHERE user_id = $1 AND friend_id = $2"
-        ).await.unwrap();
+        let mut client = self.master_pool.get().await.map_err(io::Error::other)?;
+        let transaction = client.transaction().await.map_err(io::Error::other)?;
 
-        client.execute(
-            &stmt,
+        let deleted_count = transaction.execute(
+            "DELETE FROM friends WHERE user_id = $1 AND friend_id = $2",
             &[&friend.get_user_id(), &friend.get_friend_id()]
-        ).await.unwrap();
+        ).await.map_err(io::Error::other)?;
+        if deleted_count > 0 {
+            change_counts(&*transaction, &friend.get_user_id(), &friend.get_friend_id(), -(deleted_count as i64)).await?;
+        }
 
-        Ok(true)
+        transaction.commit().await.map_err(io::Error::other)?;
+        Ok(deleted_count > 0)
     }
 
     async fn is_persistant(&self, friend: &Friend) -> Result<bool, io::Error> {
@@ -128,4 +186,209 @@ impl FriendStorage for PostgresFriendStorage {
             Err(_) => Ok(false),
         }
     }
+
+    async fn get_counts(&self, user_id: &Uuid) -> Result<FriendCounts, io::Error> {
+        let client = self.replica_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            "SELECT followers_count, following_count FROM friend_counts WHERE user_id = $1"
+        ).await.map_err(io::Error::other)?;
+
+        match client.query_opt(&stmt, &[user_id]).await.map_err(io::Error::other)? {
+            Some(row) => {
+                let followers_count: i64 = row.get(0);
+                let following_count: i64 = row.get(1);
+                Ok(FriendCounts::new(followers_count.max(0) as u64, following_count.max(0) as u64))
+            }
+            None => Ok(FriendCounts::default()),
+        }
+    }
+
+    async fn get_request_by_id(&self, id: &Uuid) -> Result<Option<FriendRequest>, io::Error> {
+        let client = self.replica_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            &format!("SELECT {} FROM friend_requests WHERE id = $1", FRIEND_REQUEST_COLUMNS)
+        ).await.map_err(io::Error::other)?;
+
+        let row = client.query_opt(&stmt, &[id]).await.map_err(io::Error::other)?;
+        row.map(friend_request_from_row).transpose()
+    }
+
+    async fn get_pending_request(&self, from_user_id: &Uuid, to_user_id: &Uuid) -> Result<Option<FriendRequest>, io::Error> {
+        // Read from master, the pending check guards request creation
+        let client = self.master_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            &format!("SELECT {} FROM friend_requests WHERE from_user_id = $1 AND to_user_id = $2 AND status = 'pending'", FRIEND_REQUEST_COLUMNS)
+        ).await.map_err(io::Error::other)?;
+
+        let row = client.query_opt(&stmt, &[from_user_id, to_user_id]).await.map_err(io::Error::other)?;
+        row.map(friend_request_from_row).transpose()
+    }
+
+    async fn get_pending_requests_by_to_user_id(&self, to_user_id: &Uuid) -> Result<Vec<FriendRequest>, io::Error> {
+        let client = self.replica_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            &format!("SELECT {} FROM friend_requests WHERE to_user_id = $1 AND status = 'pending' ORDER BY time_created DESC", FRIEND_REQUEST_COLUMNS)
+        ).await.map_err(io::Error::other)?;
+
+        let rows = client.query(&stmt, &[to_user_id]).await.map_err(io::Error::other)?;
+        rows.into_iter().map(friend_request_from_row).collect()
+    }
+
+    async fn get_pending_requests_by_from_user_id(&self, from_user_id: &Uuid) -> Result<Vec<FriendRequest>, io::Error> {
+        let client = self.replica_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            &format!("SELECT {} FROM friend_requests WHERE from_user_id = $1 AND status = 'pending' ORDER BY time_created DESC", FRIEND_REQUEST_COLUMNS)
+        ).await.map_err(io::Error::other)?;
+
+        let rows = client.query(&stmt, &[from_user_id]).await.map_err(io::Error::other)?;
+        rows.into_iter().map(friend_request_from_row).collect()
+    }
+
+    async fn create_request(&self, request: &FriendRequest) -> Result<Uuid, io::Error> {
+        let client = self.master_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            "INSERT INTO friend_requests (id, from_user_id, to_user_id, status, time_created, time_updated) VALUES ($1, $2, $3, $4, $5, $6)"
+        ).await.map_err(io::Error::other)?;
+
+        client.execute(
+            &stmt,
+            &[
+                &request.get_id(),
+                &request.get_from_user_id(),
+                &request.get_to_user_id(),
+                &request.get_status().to_string(),
+                request.get_time_created(),
+                request.get_time_updated(),
+            ]
+        ).await.map_err(io::Error::other)?;
+
+        Ok(request.get_id())
+    }
+
+    async fn update_request_status(&self, request: &FriendRequest) -> Result<bool, io::Error> {
+        let client = self.master_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            "UPDATE friend_requests SET status = $2, time_updated = $3 WHERE id = $1 AND status = 'pending'"
+        ).await.map_err(io::Error::other)?;
+
+        let updated_count = client.execute(
+            &stmt,
+            &[&request.get_id(), &request.get_status().to_string(), request.get_time_updated()]
+        ).await.map_err(io::Error::other)?;
+
+        Ok(updated_count > 0)
+    }
+
+    async fn accept_request(&self, request: &FriendRequest, friends: &[Friend]) -> Result<bool, io::Error> {
+        let mut client = self.master_pool.get().await.map_err(io::Error::other)?;
+        let transaction = client.transaction().await.map_err(io::Error::other)?;
+
+        // The row lock taken here serializes concurrent accepts of the same request
+        let updated_count = transaction.execute(
+            "UPDATE friend_requests SET status = $2, time_updated = $3 WHERE id = $1 AND status = 'pending'",
+            &[&request.get_id(), &request.get_status().to_string(), request.get_time_updated()]
+        ).await.map_err(io::Error::other)?;
+        if updated_count == 0 {
+            return Ok(false);
+        }
+
+        for friend in friends.iter() {
+            let inserted_count = transaction.execute(
+                "INSERT INTO friends (id, user_id, friend_id) SELECT $1, $2, $3 \
+                WHERE NOT EXISTS (SELECT 1 FROM friends WHERE user_id = $2 AND friend_id = $3)",
+                &[&friend.get_id(), &friend.get_user_id(), &friend.get_friend_id()]
+            ).await.map_err(io::Error::other)?;
+            if inserted_count > 0 {
+                change_counts(&*transaction, &friend.get_user_id(), &friend.get_friend_id(), 1).await?;
+            }
+        }
+
+        transaction.commit().await.map_err(io::Error::other)?;
+        Ok(true)
+    }
+
+    async fn delete_requests_by_user_id(&self, user_id: &Uuid) -> Result<u64, io::Error> {
+        let client = self.master_pool.get().await.map_err(io::Error::other)?;
+
+        client.execute(
+            "DELETE FROM friend_requests WHERE from_user_id = $1 OR to_user_id = $1",
+            &[user_id]
+        ).await.map_err(io::Error::other)
+    }
+
+    async fn get_block(&self, user_id: &Uuid, blocked_user_id: &Uuid) -> Result<Option<UserBlock>, io::Error> {
+        let client = self.replica_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            "SELECT id, user_id, blocked_user_id, time_created FROM user_blocks WHERE user_id = $1 AND blocked_user_id = $2"
+        ).await.map_err(io::Error::other)?;
+
+        Ok(client.query_opt(&stmt, &[user_id, blocked_user_id]).await.map_err(io::Error::other)?.map(user_block_from_row))
+    }
+
+    async fn get_blocks_by_user_id(&self, user_id: &Uuid) -> Result<Vec<UserBlock>, io::Error> {
+        let client = self.replica_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            "SELECT id, user_id, blocked_user_id, time_created FROM user_blocks WHERE user_id = $1 ORDER BY time_created DESC"
+        ).await.map_err(io::Error::other)?;
+
+        let rows = client.query(&stmt, &[user_id]).await.map_err(io::Error::other)?;
+        Ok(rows.into_iter().map(user_block_from_row).collect())
+    }
+
+    async fn get_blocks_by_blocked_user_id(&self, blocked_user_id: &Uuid) -> Result<Vec<UserBlock>, io::Error> {
+        let client = self.replica_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            "SELECT id, user_id, blocked_user_id, time_created FROM user_blocks WHERE blocked_user_id = $1"
+        ).await.map_err(io::Error::other)?;
+
+        let rows = client.query(&stmt, &[blocked_user_id]).await.map_err(io::Error::other)?;
+        Ok(rows.into_iter().map(user_block_from_row).collect())
+    }
+
+    async fn create_block(&self, block: &UserBlock) -> Result<Uuid, io::Error> {
+        let client = self.master_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            "INSERT INTO user_blocks (id, user_id, blocked_user_id, time_created) VALUES ($1, $2, $3, $4) \
+            ON CONFLICT (user_id, blocked_user_id) DO UPDATE SET user_id = EXCLUDED.user_id \
+            RETURNING id"
+        ).await.map_err(io::Error::other)?;
+
+        let row = client.query_one(
+            &stmt,
+            &[&block.get_id(), &block.get_user_id(), &block.get_blocked_user_id(), block.get_time_created()]
+        ).await.map_err(io::Error::other)?;
+
+        Ok(row.get(0))
+    }
+
+    async fn delete_block(&self, user_id: &Uuid, blocked_user_id: &Uuid) -> Result<bool, io::Error> {
+        let client = self.master_pool.get().await.map_err(io::Error::other)?;
+
+        let deleted_count = client.execute(
+            "DELETE FROM user_blocks WHERE user_id = $1 AND blocked_user_id = $2",
+            &[user_id, blocked_user_id]
+        ).await.map_err(io::Error::other)?;
+
+        Ok(deleted_count > 0)
+    }
+
+    async fn delete_blocks_by_user_id(&self, user_id: &Uuid) -> Result<u64, io::Error> {
+        let client = self.master_pool.get().await.map_err(io::Error::other)?;
+
+        client.execute(
+            "DELETE FROM user_blocks WHERE user_id = $1 OR blocked_user_id = $1",
+            &[user_id]
+        ).await.map_err(io::Error::other)
+    }
 }
\ No newline at end of file
@@ -11,10 +11,12 @@ impl From<Row> for Session {
         let id: Uuid = row.get(0);
         let user_id: Uuid = row.get(1);
         let data: serde_json::Value = row.get(2);
-        Session::new(
+        Session::restore(
             id.to_string(),
             user_id.to_string(),
-            data.to_string()
+            data.to_string(),
+            row.get(3),
+            row.get(4),
         )
     }
 }
@@ -38,7 +40,9 @@ impl SessionStorage for PostgresSessionStorage {
     async fn get_by_id(&self, id: &str) -> Result<Option<Session>, io::Error> {
         let client = self.replica_pool.get().await.unwrap();
 
-        let stmt = client.prepare("SELECT * FROM sessions WHERE id = $1").await.unwrap();
+        let stmt = client.prepare(
+            "SELECT id, user_id, data, time_created, time_updated FROM sessions WHERE id = $1"
+        ).await.unwrap();
 
         if let Ok(row) = client.query_one(&stmt, &[&Uuid::parse_str(id).unwrap()]).await {
             Ok(Some(Session::from(row)))
@@ -47,18 +51,85 @@ impl SessionStorage for PostgresSessionStorage {
         }
     }
 
+    async fn get_by_user_id(&self, user_id: &Uuid) -> Result<Vec<Session>, io::Error> {
+        let client = self.replica_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            "SELECT id, user_id, data, time_created, time_updated FROM sessions WHERE user_id = $1"
+        ).await.map_err(io::Error::other)?;
+
+        let rows = client.query(&stmt, &[user_id]).await.map_err(io::Error::other)?;
+
+        let mut sessions = Vec::new();
+        for row in rows {
+            sessions.push(Session::from(row));
+        }
+
+        Ok(sessions)
+    }
+
+    async fn get_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Session>, io::Error> {
+        let client = self.replica_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            "SELECT id, user_id, data, time_created, time_updated FROM sessions WHERE $1::uuid IS NULL OR id > $1 ORDER BY id LIMIT $2"
+        ).await.map_err(io::Error::other)?;
+
+        let rows = client.query(&stmt, &[&after_id, &(limit as i64)]).await.map_err(io::Error::other)?;
+
+        Ok(rows.into_iter().map(Session::from).collect())
+    }
+
     async fn create(&self, session: &Session) -> Result<Uuid, io::Error> {
         let client = self.master_pool.get().await.unwrap();
-        
+
         let stmt = client.prepare(
-            "INSERT INTO sessions (id, user_id, data) VALUES ($1, $2, $3)"
+            "INSERT INTO sessions (id, user_id, data, time_created, time_updated) VALUES ($1, $2, $3, $4, $5)"
         ).await.unwrap();
 
         client.execute(
             &stmt,
-            &[&session.get_id(), &session.get_user_id(), &session.get_data()]
+            &[
+                &session.get_id(),
+                &session.get_user_id(),
+                &session.get_data(),
+                session.get_time_created(),
+                session.get_time_updated(),
+            ]
         ).await.unwrap();
 
         Ok(session.get_id())
     }
+
+    async fn touch(&self, id: &Uuid, time_updated: &chrono::NaiveDateTime) -> Result<bool, io::Error> {
+        let client = self.master_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            "UPDATE sessions SET time_updated = $2 WHERE id = $1"
+        ).await.map_err(io::Error::other)?;
+
+        let rows_count = client.execute(&stmt, &[id, time_updated]).await.map_err(io::Error::other)?;
+
+        Ok(0 < rows_count)
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<bool, io::Error> {
+        let client = self.master_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare("DELETE FROM sessions WHERE id = $1").await.map_err(io::Error::other)?;
+
+        let rows_count = client.execute(&stmt, &[id]).await.map_err(io::Error::other)?;
+
+        Ok(0 < rows_count)
+    }
+
+    async fn delete_by_user_id(&self, user_id: &Uuid, except_id: Option<&Uuid>) -> Result<u64, io::Error> {
+        let client = self.master_pool.get().await.map_err(io::Error::other)?;
+
+        let stmt = client.prepare(
+            "DELETE FROM sessions WHERE user_id = $1 AND ($2::uuid IS NULL OR id <> $2)"
+        ).await.map_err(io::Error::other)?;
+
+        client.execute(&stmt, &[user_id, &except_id]).await.map_err(io::Error::other)
+    }
 }
\ No newline at end of file
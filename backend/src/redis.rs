@@ -52,6 +52,17 @@ pub async fn del(key: &str, conn: &mut Connection) -> Result<(), deadpool_redis:
     )
 }
 
+pub async fn del_all(keys: &[String], conn: &mut Connection) -> Result<(), deadpool_redis::redis::RedisError> {
+    if keys.is_empty() {
+        return Ok(());
+    }
+    log::debug!("Deleting keys: {:?}", keys);
+    cmd("DEL")
+        .arg(keys)
+        .query_async(conn)
+        .await
+}
+
 pub async fn s_is_member(key: &str, value: &str, conn: &mut Connection) -> Result<bool, deadpool_redis::redis::RedisError> {
     Ok(
         cmd("SISMEMBER")
@@ -202,4 +213,73 @@ pub async fn h_trim(key: &str, maxlen: &i64, conn: &mut Connection) -> Result<()
             .query_async(conn)
             .await.unwrap()
     )
-}
\ No newline at end of file
+}
+
+pub async fn incr(key: &str, conn: &mut Connection) -> Result<u64, deadpool_redis::redis::RedisError> {
+    cmd("INCR")
+        .arg(&[key])
+        .query_async(conn)
+        .await
+}
+
+pub async fn expire(key: &str, seconds: &u64, conn: &mut Connection) -> Result<(), deadpool_redis::redis::RedisError> {
+    cmd("EXPIRE")
+        .arg(&[key, seconds.to_string().as_str()])
+        .query_async(conn)
+        .await
+}
+
+pub async fn ttl(key: &str, conn: &mut Connection) -> Result<i64, deadpool_redis::redis::RedisError> {
+    cmd("TTL")
+        .arg(&[key])
+        .query_async(conn)
+        .await
+}
+
+pub async fn set_ex(key: &str, value: &str, seconds: &u64, conn: &mut Connection) -> Result<(), deadpool_redis::redis::RedisError> {
+    cmd("SET")
+        .arg(&[key, value, "EX", seconds.to_string().as_str()])
+        .query_async(conn)
+        .await
+}
+
+pub async fn get_optional(key: &str, conn: &mut Connection) -> Result<Option<String>, deadpool_redis::redis::RedisError> {
+    cmd("GET")
+        .arg(&[key])
+        .query_async(conn)
+        .await
+}
+
+pub async fn ping(conn: &mut Connection) -> Result<(), deadpool_redis::redis::RedisError> {
+    cmd("PING")
+        .query_async(conn)
+        .await
+}
+
+pub async fn scan(cursor: &u64, pattern: &str, count: &usize, conn: &mut Connection) -> Result<(u64, Vec<String>), deadpool_redis::redis::RedisError> {
+    cmd("SCAN")
+        .arg(&[cursor.to_string().as_str(), "MATCH", pattern, "COUNT", count.to_string().as_str()])
+        .query_async(conn)
+        .await
+}
+
+pub async fn z_add(key: &str, score: &i64, member: &str, conn: &mut Connection) -> Result<(), deadpool_redis::redis::RedisError> {
+    cmd("ZADD")
+        .arg(&[key, score.to_string().as_str(), member])
+        .query_async(conn)
+        .await
+}
+
+pub async fn z_remove(key: &str, member: &str, conn: &mut Connection) -> Result<(), deadpool_redis::redis::RedisError> {
+    cmd("ZREM")
+        .arg(&[key, member])
+        .query_async(conn)
+        .await
+}
+
+pub async fn z_range_by_lex(key: &str, min: &str, max: &str, count: &usize, conn: &mut Connection) -> Result<Vec<String>, deadpool_redis::redis::RedisError> {
+    cmd("ZRANGEBYLEX")
+        .arg(&[key, min, max, "LIMIT", "0", count.to_string().as_str()])
+        .query_async(conn)
+        .await
+}
@@ -0,0 +1,208 @@
+use deadpool_redis::{Connection, Pool};
+use futures::io;
+use tonic::async_trait;
+use uuid::Uuid;
+
+use crate::redis;
+use crate::session::Session;
+use crate::session_storage::SessionStorage;
+
+pub const SESSION_KEY_PREFIX: &str = "session:";
+pub const SESSION_USER_INDEX_KEY_PREFIX: &str = "session:user:";
+pub const SESSION_ID_INDEX_KEY: &str = "session:ids";
+
+pub struct RedisSessionStorage {
+    pool: &'static Pool,
+}
+
+impl RedisSessionStorage {
+    pub fn new(pool: &'static Pool) -> Self {
+        Self {
+            pool,
+        }
+    }
+
+    async fn get_connection(&self) -> Result<Connection, io::Error> {
+        self.pool.get().await.map_err(|err| io::Error::new(io::ErrorKind::NotConnected, err))
+    }
+
+    async fn read(&self, id: &Uuid, conn: &mut Connection) -> Result<Option<Session>, io::Error> {
+        match redis::get_optional(get_session_key(id).as_str(), conn).await.map_err(to_io_error)? {
+            Some(value) => serde_json::from_str::<Session>(&value)
+                .map(Some)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            None => Ok(None),
+        }
+    }
+
+    // Key TTL follows the session expiry, so Redis drops stale sessions on its own
+    async fn write(&self, session: &Session, conn: &mut Connection) -> Result<(), io::Error> {
+        let ttl_seconds = (session.get_time_expires() - chrono::Utc::now().naive_utc()).num_seconds().max(1) as u64;
+        let value = serde_json::to_string(session).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let index_key = get_user_index_key(&session.get_user_id());
+
+        redis::set_ex(get_session_key(&session.get_id()).as_str(), value.as_str(), &ttl_seconds, conn).await.map_err(to_io_error)?;
+        redis::s_add(index_key.as_str(), session.get_id().to_string().as_str(), conn).await.map_err(to_io_error)?;
+        // All members share one score, so the set is ordered by id and paged with ZRANGEBYLEX
+        redis::z_add(SESSION_ID_INDEX_KEY, &0, session.get_id().to_string().as_str(), conn).await.map_err(to_io_error)?;
+        // The index outlives every session it refers to; members of expired sessions are pruned on read
+        if redis::ttl(index_key.as_str(), conn).await.map_err(to_io_error)? < ttl_seconds as i64 {
+            redis::expire(index_key.as_str(), &ttl_seconds, conn).await.map_err(to_io_error)?;
+        }
+
+        Ok(())
+    }
+
+    async fn remove(&self, id: &Uuid, user_id: &Uuid, conn: &mut Connection) -> Result<(), io::Error> {
+        redis::del(get_session_key(id).as_str(), conn).await.map_err(to_io_error)?;
+        redis::z_remove(SESSION_ID_INDEX_KEY, id.to_string().as_str(), conn).await.map_err(to_io_error)?;
+        redis::s_remove(get_user_index_key(user_id).as_str(), id.to_string().as_str(), conn).await.map_err(to_io_error)
+    }
+
+    // Sessions written before the id index existed are added to it with a single scan per migration pass
+    async fn index_existing_ids(&self, conn: &mut Connection) -> Result<(), io::Error> {
+        let pattern = SESSION_KEY_PREFIX.to_owned() + "*";
+        let mut cursor = 0;
+        loop {
+            let (next_cursor, keys) = redis::scan(&cursor, pattern.as_str(), &1000, conn).await.map_err(to_io_error)?;
+            for id in keys.iter().filter_map(|key| Uuid::parse_str(&key[SESSION_KEY_PREFIX.len()..]).ok()) {
+                redis::z_add(SESSION_ID_INDEX_KEY, &0, id.to_string().as_str(), conn).await.map_err(to_io_error)?;
+            }
+            if next_cursor == 0 {
+                return Ok(());
+            }
+            cursor = next_cursor;
+        }
+    }
+}
+
+fn get_session_key(id: &Uuid) -> String {
+    SESSION_KEY_PREFIX.to_owned() + id.to_string().as_str()
+}
+
+fn get_user_index_key(user_id: &Uuid) -> String {
+    SESSION_USER_INDEX_KEY_PREFIX.to_owned() + user_id.to_string().as_str()
+}
+
+fn to_io_error(err: deadpool_redis::redis::RedisError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+#[async_trait]
+impl SessionStorage for RedisSessionStorage {
+    async fn get_by_id(&self, id: &str) -> Result<Option<Session>, io::Error> {
+        let id = match Uuid::parse_str(id) {
+            Ok(id) => id,
+            Err(_) => return Ok(None),
+        };
+        let mut conn = self.get_connection().await?;
+
+        self.read(&id, &mut conn).await
+    }
+
+    async fn get_by_user_id(&self, user_id: &Uuid) -> Result<Vec<Session>, io::Error> {
+        let mut conn = self.get_connection().await?;
+        let index_key = get_user_index_key(user_id);
+
+        let mut sessions = Vec::new();
+        for member in redis::s_members(index_key.as_str(), &mut conn).await.map_err(to_io_error)? {
+            let id = match Uuid::parse_str(&member) {
+                Ok(id) => id,
+                Err(_) => continue,
+            };
+            match self.read(&id, &mut conn).await? {
+                Some(session) => sessions.push(session),
+                // The session key has expired, drop it from the index as well
+                None => redis::s_remove(index_key.as_str(), member.as_str(), &mut conn).await.map_err(to_io_error)?,
+            }
+        }
+
+        Ok(sessions)
+    }
+
+    // Pages over the id index; ids of expired sessions are pruned on the way, so a batch is shorter
+    // than the limit only once the index is exhausted
+    async fn get_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Session>, io::Error> {
+        let mut conn = self.get_connection().await?;
+        if after_id.is_none() {
+            self.index_existing_ids(&mut conn).await?;
+        }
+
+        let mut sessions = Vec::new();
+        let mut min = after_id.map_or_else(|| "-".to_owned(), |id| "(".to_owned() + id.to_string().as_str());
+        while sessions.len() < limit {
+            let count = limit - sessions.len();
+            let members = redis::z_range_by_lex(SESSION_ID_INDEX_KEY, min.as_str(), "+", &count, &mut conn).await.map_err(to_io_error)?;
+            for member in members.iter() {
+                let session = match Uuid::parse_str(member) {
+                    Ok(id) => self.read(&id, &mut conn).await?,
+                    Err(_) => None,
+                };
+                match session {
+                    Some(session) => sessions.push(session),
+                    None => redis::z_remove(SESSION_ID_INDEX_KEY, member.as_str(), &mut conn).await.map_err(to_io_error)?,
+                }
+            }
+            match members.last() {
+                Some(last) if members.len() == count => min = "(".to_owned() + last.as_str(),
+                _ => break,
+            }
+        }
+
+        Ok(sessions)
+    }
+
+    async fn create(&self, session: &Session) -> Result<Uuid, io::Error> {
+        let mut conn = self.get_connection().await?;
+
+        self.write(session, &mut conn).await?;
+
+        Ok(session.get_id())
+    }
+
+    async fn touch(&self, id: &Uuid, time_updated: &chrono::NaiveDateTime) -> Result<bool, io::Error> {
+        let mut conn = self.get_connection().await?;
+
+        match self.read(id, &mut conn).await? {
+            Some(mut session) => {
+                session.set_time_updated(*time_updated);
+                self.write(&session, &mut conn).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<bool, io::Error> {
+        let mut conn = self.get_connection().await?;
+
+        match self.read(id, &mut conn).await? {
+            Some(session) => {
+                self.remove(id, &session.get_user_id(), &mut conn).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete_by_user_id(&self, user_id: &Uuid, except_id: Option<&Uuid>) -> Result<u64, io::Error> {
+        let mut conn = self.get_connection().await?;
+
+        let mut deleted_count = 0;
+        for member in redis::s_members(get_user_index_key(user_id).as_str(), &mut conn).await.map_err(to_io_error)? {
+            let id = match Uuid::parse_str(&member) {
+                Ok(id) => id,
+                Err(_) => continue,
+            };
+            if Some(&id) == except_id {
+                continue;
+            }
+            if redis::exists(get_session_key(&id).as_str(), &mut conn).await.map_err(to_io_error)? {
+                deleted_count += 1;
+            }
+            self.remove(&id, user_id, &mut conn).await?;
+        }
+
+        Ok(deleted_count)
+    }
+}
@@ -1,5 +1,6 @@
 use std::str::FromStr;
 use futures::io;
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use tokio::sync::OnceCell;
@@ -8,6 +9,11 @@ use crate::session_storage::SessionStorage;
 
 static STORAGE: OnceCell<Box<dyn SessionStorage + Send + Sync>> = OnceCell::const_new();
 
+lazy_static! {
+    pub static ref SESSION_TTL_SECONDS: i64 = std::env::var("SESSION_TTL_SECONDS").unwrap_or_else(|_| "86400".to_string()).parse::<i64>().unwrap_or(86400);
+    pub static ref SESSION_REFRESH_INTERVAL_SECONDS: i64 = std::env::var("SESSION_REFRESH_INTERVAL_SECONDS").unwrap_or_else(|_| "60".to_string()).parse::<i64>().unwrap_or(60);
+}
+
 pub async fn init_storage(storage: Box<dyn SessionStorage + Send + Sync>) {
     if !STORAGE.initialized() {
         STORAGE.get_or_init(|| async {storage}).await;
@@ -18,19 +24,34 @@ fn get_storage() -> &'static Box<dyn SessionStorage + Send + Sync> {
     STORAGE.get().unwrap()
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
     id: Uuid,
     user_id: Uuid,
     data: serde_json::Value,
+    time_created: chrono::NaiveDateTime,
+    time_updated: chrono::NaiveDateTime,
 }
 
 impl Session {
     pub fn new(id: String, user_id: String, data: String) -> Session {
+        let now = chrono::Utc::now().naive_utc();
+        Session::restore(id, user_id, data, now, now)
+    }
+
+    pub fn restore(
+        id: String,
+        user_id: String,
+        data: String,
+        time_created: chrono::NaiveDateTime,
+        time_updated: chrono::NaiveDateTime,
+    ) -> Session {
         Session {
             id: Uuid::parse_str(&id).unwrap(),
             user_id: Uuid::from_str(&user_id).unwrap(),
-            data: serde_json::Value::String(data),
+            data: serde_json::from_str(&data).unwrap_or_else(|_| serde_json::Value::String(data)),
+            time_created,
+            time_updated,
         }
     }
 
@@ -46,11 +67,90 @@ impl Session {
         &self.data
     }
 
+    pub fn get_time_created(&self) -> &chrono::NaiveDateTime {
+        &self.time_created
+    }
+
+    pub fn get_time_updated(&self) -> &chrono::NaiveDateTime {
+        &self.time_updated
+    }
+
+    pub fn set_time_updated(&mut self, time_updated: chrono::NaiveDateTime) {
+        self.time_updated = time_updated;
+    }
+
+    pub fn get_time_expires(&self) -> chrono::NaiveDateTime {
+        self.time_updated + chrono::Duration::seconds(*SESSION_TTL_SECONDS)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.get_time_expires() <= chrono::Utc::now().naive_utc()
+    }
+
+    fn is_refresh_needed(&self) -> bool {
+        self.time_updated + chrono::Duration::seconds(*SESSION_REFRESH_INTERVAL_SECONDS) <= chrono::Utc::now().naive_utc()
+    }
+
     pub async fn create(session: &Session) -> Result<Uuid, io::Error> {
         get_storage().create(session).await
     }
 
     pub async fn get_by_id(id: &str) -> Result<Option<Session>, io::Error> {
-        get_storage().get_by_id(id).await
+        if Uuid::parse_str(id).is_err() {
+            log::debug!("session id '{}' is malformed", id);
+            return Ok(None);
+        }
+
+        let mut session = match get_storage().get_by_id(id).await? {
+            Some(session) => session,
+            None => return Ok(None),
+        };
+
+        if session.is_expired() {
+            log::debug!("session '{}' is expired", id);
+            get_storage().delete(&session.id).await?;
+            return Ok(None);
+        }
+
+        if session.is_refresh_needed() {
+            let time_updated = chrono::Utc::now().naive_utc();
+            get_storage().touch(&session.id, &time_updated).await?;
+            session.set_time_updated(time_updated);
+        }
+
+        Ok(Some(session))
+    }
+
+    // Unlike get_by_id neither slides the expiry nor deletes an expired session, for looking at sessions
+    // other than the one making the request
+    pub async fn read_by_id(id: &str) -> Result<Option<Session>, io::Error> {
+        if Uuid::parse_str(id).is_err() {
+            return Ok(None);
+        }
+
+        Ok(get_storage().get_by_id(id).await?.filter(|session| !session.is_expired()))
+    }
+
+    // Active sessions of the user, most recently used first. Read-only as well, nothing is touched
+    pub async fn get_by_user_id(user_id: &Uuid) -> Result<Vec<Session>, io::Error> {
+        let mut sessions: Vec<Session> = get_storage().get_by_user_id(user_id).await?
+            .into_iter()
+            .filter(|session| !session.is_expired())
+            .collect();
+        sessions.sort_by(|a, b| b.time_updated.cmp(&a.time_updated));
+
+        Ok(sessions)
+    }
+
+    pub async fn delete(id: &Uuid) -> Result<bool, io::Error> {
+        get_storage().delete(id).await
+    }
+
+    pub async fn delete_by_user_id(user_id: &Uuid) -> Result<u64, io::Error> {
+        get_storage().delete_by_user_id(user_id, None).await
+    }
+
+    pub async fn delete_by_user_id_except(user_id: &Uuid, except_id: &Uuid) -> Result<u64, io::Error> {
+        get_storage().delete_by_user_id(user_id, Some(except_id)).await
     }
 }
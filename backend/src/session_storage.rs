@@ -7,5 +7,10 @@ use crate::session::Session;
 #[async_trait]
 pub trait SessionStorage {
     async fn get_by_id(&self, id: &str) -> Result<Option<Session>, Error>;
+    async fn get_by_user_id(&self, user_id: &Uuid) -> Result<Vec<Session>, Error>;
+    async fn get_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Session>, Error>;
     async fn create(&self, session: &Session) -> Result<Uuid, Error>;
+    async fn touch(&self, id: &Uuid, time_updated: &chrono::NaiveDateTime) -> Result<bool, Error>;
+    async fn delete(&self, id: &Uuid) -> Result<bool, Error>;
+    async fn delete_by_user_id(&self, user_id: &Uuid, except_id: Option<&Uuid>) -> Result<u64, Error>;
 }
@@ -0,0 +1,206 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use futures::io;
+use tokio::sync::OnceCell;
+
+use crate::dual_write_friend_storage::DualWriteFriendStorage;
+use crate::dual_write_session_storage::DualWriteSessionStorage;
+use crate::friend;
+use crate::friend_storage::FriendStorage;
+use crate::memory_friend_storage::MemoryFriendStorage;
+use crate::memory_session_storage::MemorySessionStorage;
+use crate::postgres;
+use crate::postgres_friend_storage::PostgresFriendStorage;
+use crate::postgres_session_storage::PostgresSessionStorage;
+use crate::redis;
+use crate::redis_session_storage::RedisSessionStorage;
+use crate::session;
+use crate::session_storage::SessionStorage;
+use crate::storage_migration;
+use crate::tarantool::{self, TarantoolClientManager};
+use crate::tarantool_friend_storage::TarantoolFriendStorage;
+use crate::tarantool_session_storage::TarantoolSessionStorage;
+
+static TARANTOOL_MANAGER: OnceCell<TarantoolClientManager> = OnceCell::const_new();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageBackend {
+    Postgres,
+    Tarantool,
+    Redis,
+    Memory,
+}
+
+impl FromStr for StorageBackend {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "postgres" => Ok(StorageBackend::Postgres),
+            "tarantool" => Ok(StorageBackend::Tarantool),
+            "redis" => Ok(StorageBackend::Redis),
+            "memory" => Ok(StorageBackend::Memory),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown storage backend '{}', expected one of: postgres, tarantool, redis, memory", other),
+            )),
+        }
+    }
+}
+
+// STORAGE_BACKEND sets the default, SESSION_STORAGE and FRIEND_STORAGE override it per storage
+fn get_backend(name: &str) -> Result<StorageBackend, io::Error> {
+    std::env::var(name)
+        .or_else(|_| std::env::var("STORAGE_BACKEND"))
+        .unwrap_or_else(|_| String::from("tarantool"))
+        .parse::<StorageBackend>()
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", name, err)))
+}
+
+async fn check_postgres() -> Result<(), io::Error> {
+    for pool in [postgres::get_master_pool_ref(), postgres::get_replica_pool_ref()] {
+        let client = pool.get().await.map_err(|err| io::Error::new(
+            io::ErrorKind::NotConnected,
+            format!("postgres storage is unreachable: {}", err),
+        ))?;
+        client.simple_query("SELECT 1").await.map_err(|err| io::Error::new(
+            io::ErrorKind::NotConnected,
+            format!("postgres storage is unreachable: {}", err),
+        ))?;
+    }
+    Ok(())
+}
+
+async fn check_redis() -> Result<(), io::Error> {
+    let mut conn = redis::get_pool_ref().get().await.map_err(|err| io::Error::new(
+        io::ErrorKind::NotConnected,
+        format!("redis storage is unreachable: {}", err),
+    ))?;
+    redis::ping(&mut conn).await.map_err(|err| io::Error::new(
+        io::ErrorKind::NotConnected,
+        format!("redis storage is unreachable: {}", err),
+    ))
+}
+
+// Session and friend storages share one cluster client and its health checks
+async fn connect_tarantool() -> Result<TarantoolClientManager, io::Error> {
+    let manager = TARANTOOL_MANAGER.get_or_try_init(|| async {
+        let manager = TarantoolClientManager::new().await;
+        manager.ping().await.map_err(|err| io::Error::new(
+            io::ErrorKind::NotConnected,
+            format!("tarantool storage is unreachable: {}", err),
+        ))?;
+        tarantool::migrate_up(&manager).await.map_err(|err| io::Error::new(
+            err.kind(),
+            format!("unable to migrate tarantool schema: {}", err),
+        ))?;
+        Ok::<TarantoolClientManager, io::Error>(manager)
+    }).await?;
+
+    Ok(manager.clone())
+}
+
+async fn create_session_storage(backend: StorageBackend) -> Result<Box<dyn SessionStorage + Send + Sync>, io::Error> {
+    Ok(match backend {
+        StorageBackend::Postgres => {
+            check_postgres().await?;
+            Box::new(PostgresSessionStorage::new(
+                postgres::get_master_pool_ref(),
+                postgres::get_replica_pool_ref(),
+            ))
+        }
+        StorageBackend::Tarantool => Box::new(TarantoolSessionStorage::new(connect_tarantool().await?)),
+        StorageBackend::Redis => {
+            check_redis().await?;
+            Box::new(RedisSessionStorage::new(redis::get_pool_ref()))
+        }
+        StorageBackend::Memory => Box::new(MemorySessionStorage::new()),
+    })
+}
+
+async fn create_friend_storage(backend: StorageBackend) -> Result<Box<dyn FriendStorage + Send + Sync>, io::Error> {
+    Ok(match backend {
+        StorageBackend::Postgres => {
+            check_postgres().await?;
+            Box::new(PostgresFriendStorage::new(
+                postgres::get_master_pool_ref(),
+                postgres::get_replica_pool_ref(),
+            ))
+        }
+        StorageBackend::Tarantool => Box::new(TarantoolFriendStorage::new(connect_tarantool().await?)),
+        StorageBackend::Redis => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "FRIEND_STORAGE: redis backend is supported for sessions only",
+            ));
+        }
+        StorageBackend::Memory => Box::new(MemoryFriendStorage::new()),
+    })
+}
+
+// <NAME>_MIGRATE_TO turns on dual writes into a second backend
+fn get_migration_backend(name: &str) -> Result<Option<StorageBackend>, io::Error> {
+    match std::env::var(name) {
+        Ok(value) if !value.is_empty() => value
+            .parse::<StorageBackend>()
+            .map(Some)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", name, err))),
+        _ => Ok(None),
+    }
+}
+
+// <NAME>_READ_FROM switches reads between the migration source and target, independently of writes
+fn is_read_from_target(name: &str) -> Result<bool, io::Error> {
+    match std::env::var(name).unwrap_or_else(|_| String::from("source")).as_str() {
+        "source" => Ok(false),
+        "target" => Ok(true),
+        other => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: unknown value '{}', expected one of: source, target", name, other),
+        )),
+    }
+}
+
+pub async fn init_session_storage() -> Result<(), io::Error> {
+    let backend = get_backend("SESSION_STORAGE")?;
+    let storage = create_session_storage(backend).await?;
+
+    match get_migration_backend("SESSION_STORAGE_MIGRATE_TO")? {
+        Some(target_backend) => {
+            let is_read_from_target = is_read_from_target("SESSION_STORAGE_READ_FROM")?;
+            let source: Arc<dyn SessionStorage + Send + Sync> = Arc::from(storage);
+            let target: Arc<dyn SessionStorage + Send + Sync> = Arc::from(create_session_storage(target_backend).await?);
+            storage_migration::spawn("session storage", source.clone(), target.clone());
+            session::init_storage(Box::new(DualWriteSessionStorage::new(source, target, is_read_from_target))).await;
+            log::info!("session storage: migrating {:?} -> {:?}, reading from {:?}", backend, target_backend,
+                if is_read_from_target { target_backend } else { backend });
+        }
+        None => {
+            session::init_storage(storage).await;
+            log::info!("session storage: {:?}", backend);
+        }
+    }
+    Ok(())
+}
+
+pub async fn init_friend_storage() -> Result<(), io::Error> {
+    let backend = get_backend("FRIEND_STORAGE")?;
+    let storage = create_friend_storage(backend).await?;
+
+    match get_migration_backend("FRIEND_STORAGE_MIGRATE_TO")? {
+        Some(target_backend) => {
+            let is_read_from_target = is_read_from_target("FRIEND_STORAGE_READ_FROM")?;
+            let source: Arc<dyn FriendStorage + Send + Sync> = Arc::from(storage);
+            let target: Arc<dyn FriendStorage + Send + Sync> = Arc::from(create_friend_storage(target_backend).await?);
+            storage_migration::spawn("friend storage", source.clone(), target.clone());
+            friend::init_storage(Box::new(DualWriteFriendStorage::new(source, target, is_read_from_target))).await;
+            log::info!("friend storage: migrating {:?} -> {:?}, reading from {:?}", backend, target_backend,
+                if is_read_from_target { target_backend } else { backend });
+        }
+        None => {
+            friend::init_storage(storage).await;
+            log::info!("friend storage: {:?}", backend);
+        }
+    }
+    Ok(())
+}
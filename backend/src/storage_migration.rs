@@ -0,0 +1,297 @@
+use std::sync::Arc;
+use futures::io;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tonic::async_trait;
+use uuid::Uuid;
+
+use crate::friend::Friend;
+use crate::friend_storage::FriendStorage;
+use crate::session::Session;
+use crate::session_storage::SessionStorage;
+
+lazy_static! {
+    pub static ref STORAGE_MIGRATION_RUN: String = std::env::var("STORAGE_MIGRATION_RUN").unwrap_or_default();
+    pub static ref STORAGE_MIGRATION_BATCH_SIZE: usize = std::env::var("STORAGE_MIGRATION_BATCH_SIZE").unwrap_or_else(|_| "500".to_string()).parse::<usize>().unwrap_or(500);
+    pub static ref STORAGE_MIGRATION_RECONCILE_FIX: bool = std::env::var("STORAGE_MIGRATION_RECONCILE_FIX").unwrap_or_else(|_| "true".to_string()).as_str() == "true";
+}
+
+// Common view of session and friend storages for backfill and reconciliation
+#[async_trait]
+pub trait MigrationStorage: Send + Sync {
+    type Record: Send + Sync;
+
+    fn get_record_id(record: &Self::Record) -> Uuid;
+    fn is_same_record(left: &Self::Record, right: &Self::Record) -> bool;
+    async fn fetch_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Self::Record>, io::Error>;
+    async fn fetch_record(&self, record: &Self::Record) -> Result<Option<Self::Record>, io::Error>;
+    async fn put_record(&self, record: &Self::Record) -> Result<(), io::Error>;
+    async fn delete_record(&self, record: &Self::Record) -> Result<(), io::Error>;
+}
+
+#[async_trait]
+impl MigrationStorage for dyn SessionStorage + Send + Sync {
+    type Record = Session;
+
+    fn get_record_id(session: &Session) -> Uuid {
+        session.get_id()
+    }
+
+    // Tarantool keeps timestamps with second precision
+    fn is_same_record(left: &Session, right: &Session) -> bool {
+        left.get_id() == right.get_id()
+            && left.get_user_id() == right.get_user_id()
+            && left.get_data() == right.get_data()
+            && left.get_time_created().and_utc().timestamp() == right.get_time_created().and_utc().timestamp()
+            && left.get_time_updated().and_utc().timestamp() == right.get_time_updated().and_utc().timestamp()
+    }
+
+    async fn fetch_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Session>, io::Error> {
+        self.get_batch(after_id, limit).await
+    }
+
+    async fn fetch_record(&self, session: &Session) -> Result<Option<Session>, io::Error> {
+        self.get_by_id(session.get_id().to_string().as_str()).await
+    }
+
+    async fn put_record(&self, session: &Session) -> Result<(), io::Error> {
+        self.delete(&session.get_id()).await?;
+        self.create(session).await?;
+        Ok(())
+    }
+
+    async fn delete_record(&self, session: &Session) -> Result<(), io::Error> {
+        self.delete(&session.get_id()).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MigrationStorage for dyn FriendStorage + Send + Sync {
+    type Record = Friend;
+
+    fn get_record_id(friend: &Friend) -> Uuid {
+        friend.get_id()
+    }
+
+    fn is_same_record(left: &Friend, right: &Friend) -> bool {
+        left == right
+    }
+
+    async fn fetch_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Friend>, io::Error> {
+        self.get_batch(after_id, limit).await
+    }
+
+    async fn fetch_record(&self, friend: &Friend) -> Result<Option<Friend>, io::Error> {
+        self.get_by_id(&friend.get_id()).await
+    }
+
+    async fn put_record(&self, friend: &Friend) -> Result<(), io::Error> {
+        self.delete(friend).await?;
+        self.create(friend).await?;
+        Ok(())
+    }
+
+    async fn delete_record(&self, friend: &Friend) -> Result<(), io::Error> {
+        self.delete(friend).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MigrationReport {
+    checked: u64,
+    missing: u64,
+    different: u64,
+    extra: u64,
+    fixed: u64,
+    failed: u64,
+}
+
+// Copies source records that are missing in the target, leaving existing ones untouched
+pub async fn backfill<S: MigrationStorage + ?Sized>(source: &S, target: &S, batch_size: usize) -> Result<MigrationReport, io::Error> {
+    let mut report = MigrationReport::default();
+    copy_missing(source, target, batch_size, false, true, &mut report).await?;
+    Ok(report)
+}
+
+// Compares both stores in batches, reporting and optionally fixing records that differ
+pub async fn reconcile<S: MigrationStorage + ?Sized>(source: &S, target: &S, batch_size: usize, is_fix_enabled: bool) -> Result<MigrationReport, io::Error> {
+    let mut report = MigrationReport::default();
+    copy_missing(source, target, batch_size, true, is_fix_enabled, &mut report).await?;
+
+    let mut after_id = None;
+    loop {
+        let records = target.fetch_batch(after_id.as_ref(), batch_size).await?;
+        // A short batch may still be followed by more records, only an empty one ends the pass
+        if records.is_empty() {
+            break;
+        }
+        for record in records.iter() {
+            if source.fetch_record(record).await?.is_some() {
+                continue;
+            }
+            report.extra += 1;
+            log::info!("record '{}' exists only in migration target", S::get_record_id(record));
+            if is_fix_enabled {
+                match target.delete_record(record).await {
+                    Ok(_) => report.fixed += 1,
+                    Err(err) => {
+                        log::warn!("unable to delete record '{}' from migration target: {:?}", S::get_record_id(record), err);
+                        report.failed += 1;
+                    }
+                }
+            }
+        }
+        after_id = records.last().map(S::get_record_id);
+    }
+
+    Ok(report)
+}
+
+async fn copy_missing<S: MigrationStorage + ?Sized>(
+    source: &S,
+    target: &S,
+    batch_size: usize,
+    is_compare_enabled: bool,
+    is_fix_enabled: bool,
+    report: &mut MigrationReport,
+) -> Result<(), io::Error> {
+    let mut after_id = None;
+    loop {
+        let records = source.fetch_batch(after_id.as_ref(), batch_size).await?;
+        if records.is_empty() {
+            break;
+        }
+        for record in records.iter() {
+            report.checked += 1;
+            match target.fetch_record(record).await? {
+                None => report.missing += 1,
+                Some(target_record) if is_compare_enabled && !S::is_same_record(record, &target_record) => {
+                    log::info!("record '{}' differs in migration target", S::get_record_id(record));
+                    report.different += 1;
+                }
+                Some(_) => continue,
+            }
+            if is_fix_enabled {
+                match target.put_record(record).await {
+                    Ok(_) => report.fixed += 1,
+                    Err(err) => {
+                        log::warn!("unable to copy record '{}' to migration target: {:?}", S::get_record_id(record), err);
+                        report.failed += 1;
+                    }
+                }
+            }
+        }
+        log::debug!("migration progress: {:?}", report);
+        after_id = records.last().map(S::get_record_id);
+    }
+
+    Ok(())
+}
+
+// STORAGE_MIGRATION_RUN selects the passes to run in background: backfill, reconcile or all.
+// Enable it on a single instance only
+pub fn spawn<S: MigrationStorage + ?Sized + 'static>(name: &'static str, source: Arc<S>, target: Arc<S>) {
+    let is_backfill_enabled = matches!(STORAGE_MIGRATION_RUN.as_str(), "backfill" | "all");
+    let is_reconcile_enabled = matches!(STORAGE_MIGRATION_RUN.as_str(), "reconcile" | "all");
+    if !is_backfill_enabled && !is_reconcile_enabled {
+        return;
+    }
+
+    tokio::spawn(async move {
+        if is_backfill_enabled {
+            match backfill(&*source, &*target, *STORAGE_MIGRATION_BATCH_SIZE).await {
+                Ok(report) => log::info!("{} backfill is finished: {:?}", name, report),
+                Err(err) => {
+                    log::error!("{} backfill is interrupted: {:?}", name, err);
+                    return;
+                }
+            }
+        }
+        if is_reconcile_enabled {
+            match reconcile(&*source, &*target, *STORAGE_MIGRATION_BATCH_SIZE, *STORAGE_MIGRATION_RECONCILE_FIX).await {
+                Ok(report) => log::info!("{} reconciliation is finished: {:?}", name, report),
+                Err(err) => log::error!("{} reconciliation is interrupted: {:?}", name, err),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_friend_storage::MemoryFriendStorage;
+    use crate::memory_session_storage::MemorySessionStorage;
+
+    fn new_friend_storage() -> Box<dyn FriendStorage + Send + Sync> {
+        Box::new(MemoryFriendStorage::new())
+    }
+
+    fn new_session_storage() -> Box<dyn SessionStorage + Send + Sync> {
+        Box::new(MemorySessionStorage::new())
+    }
+
+    fn new_session(data: &str) -> Session {
+        Session::new(Uuid::new_v4().to_string(), Uuid::new_v4().to_string(), data.to_string())
+    }
+
+    #[tokio::test]
+    async fn backfill_copies_only_missing_records() {
+        let source = new_friend_storage();
+        let target = new_friend_storage();
+        let friends: Vec<Friend> = (0..5).map(|_| Friend::new(None, Uuid::new_v4(), Uuid::new_v4())).collect();
+        for friend in friends.iter() {
+            source.create(friend).await.unwrap();
+        }
+        target.create(&friends[0]).await.unwrap();
+
+        let report = backfill(&*source, &*target, 2).await.unwrap();
+
+        assert_eq!((report.checked, report.missing, report.fixed, report.failed), (5, 4, 4, 0));
+        for friend in friends.iter() {
+            assert_eq!(target.get_by_id(&friend.get_id()).await.unwrap().as_ref(), Some(friend));
+        }
+    }
+
+    #[tokio::test]
+    async fn reconcile_fixes_different_and_extra_records() {
+        let source = new_friend_storage();
+        let target = new_friend_storage();
+        let friend = Friend::new(None, Uuid::new_v4(), Uuid::new_v4());
+        let changed_friend = Friend::new(Some(friend.get_id()), friend.get_user_id(), Uuid::new_v4());
+        let extra_friend = Friend::new(None, Uuid::new_v4(), Uuid::new_v4());
+        source.create(&friend).await.unwrap();
+        target.create(&changed_friend).await.unwrap();
+        target.create(&extra_friend).await.unwrap();
+
+        let report = reconcile(&*source, &*target, 1, true).await.unwrap();
+
+        assert_eq!((report.checked, report.different, report.extra, report.fixed), (1, 1, 1, 2));
+        assert_eq!(target.get_by_id(&friend.get_id()).await.unwrap(), Some(friend));
+        assert_eq!(target.get_by_id(&extra_friend.get_id()).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn reconcile_only_reports_when_fix_is_disabled() {
+        let source = new_session_storage();
+        let target = new_session_storage();
+        let session = new_session("{\"theme\":\"dark\"}");
+        let changed_session = Session::restore(
+            session.get_id().to_string(),
+            session.get_user_id().to_string(),
+            "{\"theme\":\"light\"}".to_string(),
+            *session.get_time_created(),
+            *session.get_time_updated(),
+        );
+        source.create(&session).await.unwrap();
+        target.create(&changed_session).await.unwrap();
+        target.create(&new_session("{}")).await.unwrap();
+
+        let report = reconcile(&*source, &*target, 10, false).await.unwrap();
+
+        assert_eq!((report.checked, report.different, report.extra, report.fixed), (1, 1, 1, 0));
+        let stored = target.get_by_id(session.get_id().to_string().as_str()).await.unwrap().unwrap();
+        assert_eq!(stored.get_data(), changed_session.get_data());
+    }
+}
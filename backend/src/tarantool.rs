@@ -1,12 +1,105 @@
-use rusty_tarantool::tarantool::{Client, ClientConfig};
-use rand::Rng as _;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use futures::io;
+use lazy_static::lazy_static;
+use rand::seq::SliceRandom as _;
+use rusty_tarantool::tarantool::{Client, ClientConfig, TarantoolResponse};
+use serde::Serialize;
 use tokio::sync::OnceCell;
 
 static REPLICA_AUTHORITY: OnceCell<Vec<TarantoolClientConfig>> = OnceCell::const_new();
 
+const UNKNOWN_LEADER: usize = usize::MAX;
+
+const SCRIPTS_UP: [(u64, &str, &str); 8] = [(
+    1,
+    "0001_create-sessions",
+    include_str!("../tarantool/0001_create-sessions.lua"),
+),(
+    2,
+    "0002_create-friends",
+    include_str!("../tarantool/0002_create-friends.lua"),
+),(
+    3,
+    "0003_create-cluster-functions",
+    include_str!("../tarantool/0003_create-cluster-functions.lua"),
+),(
+    4,
+    "0004_create-batch-functions",
+    include_str!("../tarantool/0004_create-batch-functions.lua"),
+),(
+    5,
+    "0005_create-friend-requests",
+    include_str!("../tarantool/0005_create-friend-requests.lua"),
+),(
+    6,
+    "0006_create-friend-counts",
+    include_str!("../tarantool/0006_create-friend-counts.lua"),
+),(
+    7,
+    "0007_create-user-blocks",
+    include_str!("../tarantool/0007_create-user-blocks.lua"),
+),(
+    8,
+    "0008_create-friend-batch-lookups",
+    include_str!("../tarantool/0008_create-friend-batch-lookups.lua"),
+)];
+
+const SCHEMA_VERSION_GET: &str = "
+    box.schema.space.create('schema_versions', { if_not_exists = true })
+    box.space.schema_versions:create_index('primary', { type = 'TREE', unique = true, parts = { 1, 'unsigned' }, if_not_exists = true })
+    local last = box.space.schema_versions.index.primary:max()
+    if last == nil then
+        return 0
+    end
+    return last[1]
+";
+
+const SCHEMA_VERSION_SET: &str = "
+    local version, name, time_applied = ...
+    box.space.schema_versions:replace{version, name, time_applied}
+";
+
+const NO_PARAMS: [u8; 0] = [];
+
+enum TarantoolRequest<'a> {
+    Call(&'a str),
+    Eval(&'a str),
+}
+
+impl TarantoolRequest<'_> {
+    async fn execute<T: Serialize + Sync>(&self, client: &Client, params: &T) -> Result<TarantoolResponse, io::Error> {
+        match self {
+            TarantoolRequest::Call(function) => client.call_fn(function, params).await,
+            TarantoolRequest::Eval(expression) => client.eval(expression, params).await,
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        match self {
+            TarantoolRequest::Call(function) => function,
+            TarantoolRequest::Eval(_) => "eval",
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref TARANTOOL_HEALTH_CHECK_INTERVAL_MS: u64 = std::env::var("TARANTOOL_HEALTH_CHECK_INTERVAL_MS").unwrap_or_else(|_| "1000".to_string()).parse::<u64>().unwrap_or(1000);
+    pub static ref TARANTOOL_WRITE_RETRIES: usize = std::env::var("TARANTOOL_WRITE_RETRIES").unwrap_or_else(|_| "3".to_string()).parse::<usize>().unwrap_or(3);
+    pub static ref TARANTOOL_WRITE_RETRY_DELAY_MS: u64 = std::env::var("TARANTOOL_WRITE_RETRY_DELAY_MS").unwrap_or_else(|_| "500".to_string()).parse::<u64>().unwrap_or(500);
+}
+
+struct TarantoolNode {
+    addr: String,
+    client: Client,
+    is_healthy: AtomicBool,
+}
+
 #[derive(Clone)]
 pub struct TarantoolClientManager {
-    clients: Vec<Client>,
+    nodes: Arc<Vec<TarantoolNode>>,
+    leader_index: Arc<AtomicUsize>,
 }
 
 impl TarantoolClientManager {
@@ -30,35 +123,198 @@ impl TarantoolClientManager {
                 .collect::<Vec<_>>()
         }).await;
 
-        Self {
-            clients: configs.into_iter().map(|config| ClientConfig::new(
-                    config.addr.clone(),
-                    config.login.clone(),
-                    config.password.clone(),
-            )
-                .set_timeout_time_ms(2000)
-                .set_reconnect_time_ms(2000)
-                .build()).collect(),
+        let manager = Self {
+            nodes: Arc::new(configs.into_iter().map(|config| TarantoolNode {
+                addr: config.addr.clone(),
+                client: ClientConfig::new(
+                        config.addr.clone(),
+                        config.login.clone(),
+                        config.password.clone(),
+                    )
+                    .set_timeout_time_ms(2000)
+                    .set_reconnect_time_ms(2000)
+                    .build(),
+                is_healthy: AtomicBool::new(true),
+            }).collect()),
+            leader_index: Arc::new(AtomicUsize::new(UNKNOWN_LEADER)),
+        };
+
+        let health_checker = manager.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_millis(*TARANTOOL_HEALTH_CHECK_INTERVAL_MS)).await;
+                health_checker.check_health().await;
+            }
+        });
+
+        manager
+    }
+
+    // Succeeds when at least one instance of the cluster answers
+    pub async fn ping(&self) -> Result<(), io::Error> {
+        let mut last_error = io::Error::new(io::ErrorKind::NotConnected, "no tarantool instances configured");
+        for node in self.nodes.iter() {
+            match node.client.ping().await {
+                Ok(_) => return Ok(()),
+                Err(err) => last_error = err,
+            }
+        }
+        Err(last_error)
+    }
+
+    // Calls a stored procedure on any healthy instance, falling back to the others on connection errors
+    pub async fn read<T: Serialize + Sync>(&self, function: &str, params: &T) -> Result<TarantoolResponse, io::Error> {
+        let mut last_error = io::Error::new(io::ErrorKind::NotConnected, "no tarantool instances configured");
+        for index in self.get_read_order() {
+            match self.nodes[index].client.call_fn(function, params).await {
+                Ok(response) => return Ok(response),
+                Err(err) if is_connection_error(&err) => {
+                    log::warn!("tarantool instance '{}' failed on '{}': {:?}", self.nodes[index].addr, function, err);
+                    self.mark_unhealthy(index);
+                    last_error = err;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Err(last_error)
+    }
+
+    // Calls a stored procedure on the leader, rediscovering it and retrying after a failover
+    pub async fn write<T: Serialize + Sync>(&self, function: &str, params: &T) -> Result<TarantoolResponse, io::Error> {
+        self.execute_on_leader(TarantoolRequest::Call(function), params).await
+    }
+
+    pub async fn eval<T: Serialize + Sync>(&self, expression: &str, params: &T) -> Result<TarantoolResponse, io::Error> {
+        self.execute_on_leader(TarantoolRequest::Eval(expression), params).await
+    }
+
+    async fn execute_on_leader<T: Serialize + Sync>(&self, request: TarantoolRequest<'_>, params: &T) -> Result<TarantoolResponse, io::Error> {
+        let mut last_error = io::Error::new(io::ErrorKind::NotConnected, "tarantool leader is unknown");
+        for attempt in 0..=*TARANTOOL_WRITE_RETRIES {
+            if 0 < attempt {
+                tokio::time::sleep(Duration::from_millis(*TARANTOOL_WRITE_RETRY_DELAY_MS)).await;
+            }
+
+            let index = match self.get_leader_index().await {
+                Ok(index) => index,
+                Err(err) => {
+                    log::warn!("unable to discover tarantool leader (attempt {}): {:?}", attempt + 1, err);
+                    last_error = err;
+                    continue;
+                }
+            };
+
+            match request.execute(&self.nodes[index].client, params).await {
+                Ok(response) => return Ok(response),
+                Err(err) if is_connection_error(&err) || is_read_only_error(&err) => {
+                    log::warn!("tarantool leader '{}' failed on '{}' (attempt {}): {:?}", self.nodes[index].addr, request.get_name(), attempt + 1, err);
+                    if is_connection_error(&err) {
+                        self.mark_unhealthy(index);
+                    }
+                    let _ = self.leader_index.compare_exchange(index, UNKNOWN_LEADER, Ordering::SeqCst, Ordering::SeqCst);
+                    last_error = err;
+                }
+                Err(err) => return Err(err),
+            }
         }
+        Err(last_error)
     }
 
-    pub fn get_client(&self) -> &Client {
-        let range_end = self.clients.len()-1;
-        if range_end == 0 {
-            return &self.clients[0];
+    async fn check_health(&self) {
+        for (index, node) in self.nodes.iter().enumerate() {
+            let is_healthy = node.client.ping().await.is_ok();
+            if is_healthy != node.is_healthy.swap(is_healthy, Ordering::SeqCst) {
+                log::info!("tarantool instance '{}' is {}", node.addr, if is_healthy { "back in rotation" } else { "out of rotation" });
+            }
+            if !is_healthy {
+                let _ = self.leader_index.compare_exchange(index, UNKNOWN_LEADER, Ordering::SeqCst, Ordering::SeqCst);
+            }
+        }
+
+        if self.leader_index.load(Ordering::SeqCst) == UNKNOWN_LEADER {
+            if let Err(err) = self.discover_leader().await {
+                log::warn!("unable to discover tarantool leader: {:?}", err);
+            }
+        }
+    }
+
+    async fn get_leader_index(&self) -> Result<usize, io::Error> {
+        match self.leader_index.load(Ordering::SeqCst) {
+            UNKNOWN_LEADER => self.discover_leader().await,
+            index => Ok(index),
+        }
+    }
+
+    // The leader is the only writable instance of the replicaset.
+    // Evaluated inline since the stored procedures may not be migrated yet
+    async fn discover_leader(&self) -> Result<usize, io::Error> {
+        for index in self.get_read_order() {
+            let is_leader = self.nodes[index].client
+                .eval("return not box.info.ro", &NO_PARAMS).await
+                .and_then(|response| response.decode_single::<bool>());
+
+            match is_leader {
+                Ok(true) => {
+                    log::info!("tarantool leader is '{}'", self.nodes[index].addr);
+                    self.leader_index.store(index, Ordering::SeqCst);
+                    return Ok(index);
+                }
+                Ok(false) => (),
+                Err(err) if is_connection_error(&err) => self.mark_unhealthy(index),
+                Err(err) => log::warn!("unable to check tarantool instance '{}' role: {:?}", self.nodes[index].addr, err),
+            }
         }
-        &self.clients[rand::thread_rng().gen_range(0..range_end)]
+        Err(io::Error::new(io::ErrorKind::NotConnected, "no writable tarantool instance found"))
     }
 
-    pub async fn get_leader_client(&self) -> &Client {
-        &self.clients[self.get_leader_index().await as usize]
+    // Healthy instances in random order, then the rest as a last resort
+    fn get_read_order(&self) -> Vec<usize> {
+        let (mut healthy, mut unhealthy): (Vec<usize>, Vec<usize>) = (0..self.nodes.len())
+            .partition(|index| self.nodes[*index].is_healthy.load(Ordering::SeqCst));
+        let mut rng = rand::thread_rng();
+        healthy.shuffle(&mut rng);
+        unhealthy.shuffle(&mut rng);
+        healthy.extend(unhealthy);
+        healthy
     }
 
-    async fn get_leader_index(&self) -> usize {
-        let leader_name: String = self.get_client().prepare_fn_call("get_leader_name").execute().await.unwrap().decode_single().unwrap();
-        let leader_index = leader_name.chars().last().unwrap().to_digit(10).unwrap() as usize - 1;
-        leader_index
+    fn mark_unhealthy(&self, index: usize) {
+        if self.nodes[index].is_healthy.swap(false, Ordering::SeqCst) {
+            log::info!("tarantool instance '{}' is out of rotation", self.nodes[index].addr);
+        }
+    }
+}
+
+// Applies schema scripts newer than the recorded version, like postgres::migrate_up
+pub async fn migrate_up(manager: &TarantoolClientManager) -> Result<(), io::Error> {
+    let current_version: u64 = manager.eval(SCHEMA_VERSION_GET, &NO_PARAMS).await?.decode_single()?;
+
+    for (version, name, script) in SCRIPTS_UP.iter() {
+        if *version <= current_version {
+            continue;
+        }
+        log::info!("applying tarantool schema version {} '{}'", version, name);
+        manager.eval(script, &NO_PARAMS).await?;
+        manager.eval(SCHEMA_VERSION_SET, &(version, name, chrono::Utc::now().timestamp())).await?;
     }
+
+    Ok(())
+}
+
+fn is_connection_error(err: &io::Error) -> bool {
+    err.kind() != io::ErrorKind::Other
+}
+
+fn is_read_only_error(err: &io::Error) -> bool {
+    err.to_string().contains("read-only")
+}
+
+pub fn parse_uuid(value: &str) -> Result<uuid::Uuid, io::Error> {
+    uuid::Uuid::parse_str(value).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn timestamp_to_datetime(timestamp: i64) -> chrono::NaiveDateTime {
+    chrono::DateTime::from_timestamp(timestamp, 0).unwrap_or_default().naive_utc()
 }
 
 pub struct TarantoolClientConfig {
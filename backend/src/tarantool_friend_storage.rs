@@ -1,11 +1,16 @@
 use futures::io;
-use rusty_tarantool::tarantool::{Client, ExecWithParamaters as _};
 use tonic::async_trait;
 use uuid::Uuid;
 
-use crate::friend::Friend;
+use crate::friend::{Friend, FriendCounts};
+use crate::friend_request::FriendRequest;
 use crate::friend_storage::FriendStorage;
-use crate::tarantool::TarantoolClientManager;
+use crate::tarantool::{self, TarantoolClientManager};
+use crate::user_block::UserBlock;
+
+type FriendTuple = (String, String, String);
+type FriendRequestTuple = (String, String, String, String, i64, i64);
+type UserBlockTuple = (String, String, String, i64);
 
 pub struct TarantoolFriendStorage {
     manager: TarantoolClientManager,
@@ -17,119 +22,266 @@ impl TarantoolFriendStorage {
             manager,
         }
     }
+}
 
-    pub fn get_client(&self) -> &Client {
-        self.manager.get_client()
-    }
+fn friend_from_tuple(friend_tuple: FriendTuple) -> Result<Friend, io::Error> {
+    Ok(Friend::new(
+        Some(tarantool::parse_uuid(friend_tuple.0.as_str())?),
+        tarantool::parse_uuid(friend_tuple.1.as_str())?,
+        tarantool::parse_uuid(friend_tuple.2.as_str())?,
+    ))
+}
 
-    pub async fn get_leader_client(&self) -> &Client {
-        self.manager.get_leader_client().await
-    }
+fn friend_request_from_tuple(request_tuple: FriendRequestTuple) -> Result<FriendRequest, io::Error> {
+    Ok(FriendRequest::restore(
+        tarantool::parse_uuid(request_tuple.0.as_str())?,
+        tarantool::parse_uuid(request_tuple.1.as_str())?,
+        tarantool::parse_uuid(request_tuple.2.as_str())?,
+        request_tuple.3.parse()?,
+        tarantool::timestamp_to_datetime(request_tuple.4),
+        tarantool::timestamp_to_datetime(request_tuple.5),
+    ))
+}
+
+fn user_block_from_tuple(block_tuple: UserBlockTuple) -> Result<UserBlock, io::Error> {
+    Ok(UserBlock::restore(
+        tarantool::parse_uuid(block_tuple.0.as_str())?,
+        tarantool::parse_uuid(block_tuple.1.as_str())?,
+        tarantool::parse_uuid(block_tuple.2.as_str())?,
+        tarantool::timestamp_to_datetime(block_tuple.3),
+    ))
 }
 
 #[async_trait]
 impl FriendStorage for TarantoolFriendStorage {
     async fn get_by_id(&self, id: &Uuid) -> Result<Option<Friend>, io::Error> {
-        if let Ok(friend_tuple) = self.get_client()
-                .prepare_fn_call("friend_get_by_id")
-                .bind(id.to_string().as_str()).unwrap()
-                .execute().await.unwrap()
-                .decode_single::<(String, String, String)>() {
-
-            Ok(Some(
-                Friend::new(
-                    Some(Uuid::parse_str(String::from(friend_tuple.0).as_str()).unwrap()),
-                    Uuid::parse_str(String::from(friend_tuple.1).as_str()).unwrap(),
-                    Uuid::parse_str(String::from(friend_tuple.2).as_str()).unwrap(),
-                )
-            ))
-        } else {
-            Ok(None)
-        }
+        let friend_tuple: Option<FriendTuple> = self.manager
+            .read("friend_get_by_id", &(id.to_string(),)).await?
+            .decode_single()?;
+
+        friend_tuple.map(friend_from_tuple).transpose()
     }
 
     async fn get_by_user_id(&self, user_id: &Uuid) -> Result<Vec<Friend>, io::Error> {
-        let friend_tuple_vec: Vec<Vec<(String, String, String)>> = self.get_client()
-            .prepare_fn_call("friend_get_by_user_id")
-            .bind(user_id.to_string().as_str()).unwrap()
-            .execute().await.unwrap()
-            .decode_result_set().unwrap_or_else(|_| Vec::new());
+        let friend_tuples: Vec<FriendTuple> = self.manager
+            .read("friend_get_by_user_id", &(user_id.to_string(),)).await?
+            .decode_single()?;
 
-        let friend_tuple_vec = friend_tuple_vec.into_iter().flatten().collect::<Vec<(String, String, String)>>();
+        friend_tuples.into_iter().map(friend_from_tuple).collect()
+    }
 
-        let friends = friend_tuple_vec.into_iter().map(|friend_tuple|  Friend::new(
-            Some(Uuid::parse_str(String::from(friend_tuple.0).as_str()).unwrap()),
-            Uuid::parse_str(String::from(friend_tuple.1).as_str()).unwrap(),
-            Uuid::parse_str(String::from(friend_tuple.2).as_str()).unwrap(),
-        )).collect();
+    async fn get_by_friend_id(&self, friend_id: &Uuid) -> Result<Vec<Friend>, io::Error> {
+        let friend_tuples: Vec<FriendTuple> = self.manager
+            .read("friend_get_by_friend_id", &(friend_id.to_string(),)).await?
+            .decode_single()?;
 
-        Ok(friends)
+        friend_tuples.into_iter().map(friend_from_tuple).collect()
     }
 
-    async fn get_by_friend_id(&self, friend_id: &Uuid) -> Result<Vec<Friend>, io::Error> {
-        let friend_tuple_vec: Vec<(String, String, String)> = self.get_client()
-            .prepare_fn_call("friend_get_by_friend_id")
-            .bind(friend_id.to_string().as_str()).unwrap()
-            .execute().await.unwrap()
-            .decode_result_set().unwrap();
+    async fn get_by_user_ids(&self, user_ids: &[Uuid]) -> Result<Vec<Friend>, io::Error> {
+        let user_ids: Vec<String> = user_ids.iter().map(|user_id| user_id.to_string()).collect();
+        let friend_tuples: Vec<FriendTuple> = self.manager
+            .read("friend_get_by_user_ids", &(user_ids,)).await?
+            .decode_single()?;
+
+        friend_tuples.into_iter().map(friend_from_tuple).collect()
+    }
 
-        let friends = friend_tuple_vec.into_iter().map(|friend_tuple|  Friend::new(
-            Some(Uuid::parse_str(String::from(friend_tuple.0).as_str()).unwrap()),
-            Uuid::parse_str(String::from(friend_tuple.1).as_str()).unwrap(),
-            Uuid::parse_str(String::from(friend_tuple.2).as_str()).unwrap(),
-        )).collect();
+    async fn get_by_friend_ids(&self, friend_ids: &[Uuid]) -> Result<Vec<Friend>, io::Error> {
+        let friend_ids: Vec<String> = friend_ids.iter().map(|friend_id| friend_id.to_string()).collect();
+        let friend_tuples: Vec<FriendTuple> = self.manager
+            .read("friend_get_by_friend_ids", &(friend_ids,)).await?
+            .decode_single()?;
 
-        Ok(friends)
+        friend_tuples.into_iter().map(friend_from_tuple).collect()
     }
 
     async fn get_by_user_id_and_friend_id(&self, user_id: &Uuid, friend_id: &Uuid) -> Result<Option<Friend>, io::Error> {
-        if let Ok(friend_tuple) = self.get_client()
-                .prepare_fn_call("friend_get_by_user_id_and_friend_id")
-                .bind(user_id.to_string().as_str()).unwrap()
-                .bind(friend_id.to_string().as_str()).unwrap()
-                .execute().await.unwrap()
-                .decode_single::<(String, String, String)>() {
-
-            Ok(Some(
-                Friend::new(
-                    Some(Uuid::parse_str(String::from(friend_tuple.0).as_str()).unwrap()),
-                    Uuid::parse_str(String::from(friend_tuple.1).as_str()).unwrap(),
-                    Uuid::parse_str(String::from(friend_tuple.2).as_str()).unwrap(),
-                )
-            ))
-        } else {
-            Ok(None)
-        }
+        let friend_tuple: Option<FriendTuple> = self.manager
+            .read("friend_get_by_user_id_and_friend_id", &(user_id.to_string(), friend_id.to_string())).await?
+            .decode_single()?;
+
+        friend_tuple.map(friend_from_tuple).transpose()
+    }
+
+    async fn get_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Friend>, io::Error> {
+        let friend_tuples: Vec<FriendTuple> = self.manager
+            .read("friend_get_batch", &(after_id.map(|id| id.to_string()), limit)).await?
+            .decode_single()?;
+
+        friend_tuples.into_iter().map(friend_from_tuple).collect()
     }
 
     async fn create(&self, friend: &Friend) -> Result<Uuid, io::Error> {
-        let friend_tuple: (String, String, String) = self.get_leader_client().await
-            .prepare_fn_call("friend_create")
-            .bind(friend.get_id().to_string().as_str()).unwrap()
-            .bind(friend.get_user_id().to_string().as_str()).unwrap()
-            .bind(friend.get_friend_id().to_string().as_str()).unwrap()
-            .execute().await.unwrap()
-            .decode_single().unwrap();
+        let friend_tuple: FriendTuple = self.manager
+            .write("friend_create", &(
+                friend.get_id().to_string(),
+                friend.get_user_id().to_string(),
+                friend.get_friend_id().to_string(),
+            )).await?
+            .decode_single()?;
 
-        Ok(uuid::Uuid::parse_str(friend_tuple.0.as_str()).unwrap())
+        tarantool::parse_uuid(friend_tuple.0.as_str())
     }
 
     async fn delete(&self, friend: &Friend) -> Result<bool, io::Error> {
-        self.get_leader_client().await
-            .prepare_fn_call("friend_delete")
-            .bind(friend.get_id().to_string().as_str()).unwrap()
-            .execute().await.unwrap();
+        let is_deleted: bool = self.manager
+            .write("friend_delete", &(friend.get_user_id().to_string(), friend.get_friend_id().to_string())).await?
+            .decode_single()?;
 
-        Ok(true)
+        Ok(is_deleted)
     }
 
     async fn is_persistant(&self, friend: &Friend) -> Result<bool, io::Error> {
-        let is_persistant: bool = self.get_client()
-            .prepare_fn_call("friend_is_persistant")
-            .bind(friend.get_id().to_string().as_str()).unwrap()
-            .execute().await.unwrap()
-            .decode_single().unwrap();
+        let is_persistant: bool = self.manager
+            .read("friend_is_persistant", &(friend.get_user_id().to_string(), friend.get_friend_id().to_string())).await?
+            .decode_single()?;
 
         Ok(is_persistant)
     }
+
+    async fn get_counts(&self, user_id: &Uuid) -> Result<FriendCounts, io::Error> {
+        let counts_tuple: (u64, u64) = self.manager
+            .read("friend_get_counts", &(user_id.to_string(),)).await?
+            .decode_single()?;
+
+        Ok(FriendCounts::new(counts_tuple.0, counts_tuple.1))
+    }
+
+    async fn get_request_by_id(&self, id: &Uuid) -> Result<Option<FriendRequest>, io::Error> {
+        let request_tuple: Option<FriendRequestTuple> = self.manager
+            .read("friend_request_get_by_id", &(id.to_string(),)).await?
+            .decode_single()?;
+
+        request_tuple.map(friend_request_from_tuple).transpose()
+    }
+
+    async fn get_pending_request(&self, from_user_id: &Uuid, to_user_id: &Uuid) -> Result<Option<FriendRequest>, io::Error> {
+        let request_tuple: Option<FriendRequestTuple> = self.manager
+            .read("friend_request_get_pending", &(from_user_id.to_string(), to_user_id.to_string())).await?
+            .decode_single()?;
+
+        request_tuple.map(friend_request_from_tuple).transpose()
+    }
+
+    async fn get_pending_requests_by_to_user_id(&self, to_user_id: &Uuid) -> Result<Vec<FriendRequest>, io::Error> {
+        let request_tuples: Vec<FriendRequestTuple> = self.manager
+            .read("friend_request_get_pending_by_to_user_id", &(to_user_id.to_string(),)).await?
+            .decode_single()?;
+
+        request_tuples.into_iter().map(friend_request_from_tuple).collect()
+    }
+
+    async fn get_pending_requests_by_from_user_id(&self, from_user_id: &Uuid) -> Result<Vec<FriendRequest>, io::Error> {
+        let request_tuples: Vec<FriendRequestTuple> = self.manager
+            .read("friend_request_get_pending_by_from_user_id", &(from_user_id.to_string(),)).await?
+            .decode_single()?;
+
+        request_tuples.into_iter().map(friend_request_from_tuple).collect()
+    }
+
+    async fn create_request(&self, request: &FriendRequest) -> Result<Uuid, io::Error> {
+        let request_tuple: FriendRequestTuple = self.manager
+            .write("friend_request_create", &(
+                request.get_id().to_string(),
+                request.get_from_user_id().to_string(),
+                request.get_to_user_id().to_string(),
+                request.get_status().to_string(),
+                request.get_time_created().and_utc().timestamp(),
+                request.get_time_updated().and_utc().timestamp(),
+            )).await?
+            .decode_single()?;
+
+        tarantool::parse_uuid(request_tuple.0.as_str())
+    }
+
+    async fn update_request_status(&self, request: &FriendRequest) -> Result<bool, io::Error> {
+        let is_updated: bool = self.manager
+            .write("friend_request_update_status", &(
+                request.get_id().to_string(),
+                request.get_status().to_string(),
+                request.get_time_updated().and_utc().timestamp(),
+            )).await?
+            .decode_single()?;
+
+        Ok(is_updated)
+    }
+
+    async fn accept_request(&self, request: &FriendRequest, friends: &[Friend]) -> Result<bool, io::Error> {
+        let friend_tuples: Vec<FriendTuple> = friends.iter()
+            .map(|friend| (friend.get_id().to_string(), friend.get_user_id().to_string(), friend.get_friend_id().to_string()))
+            .collect();
+
+        let is_accepted: bool = self.manager
+            .write("friend_request_accept", &(
+                request.get_id().to_string(),
+                request.get_status().to_string(),
+                request.get_time_updated().and_utc().timestamp(),
+                friend_tuples,
+            )).await?
+            .decode_single()?;
+
+        Ok(is_accepted)
+    }
+
+    async fn delete_requests_by_user_id(&self, user_id: &Uuid) -> Result<u64, io::Error> {
+        let deleted_count: u64 = self.manager
+            .write("friend_request_delete_by_user_id", &(user_id.to_string(),)).await?
+            .decode_single()?;
+
+        Ok(deleted_count)
+    }
+
+    async fn get_block(&self, user_id: &Uuid, blocked_user_id: &Uuid) -> Result<Option<UserBlock>, io::Error> {
+        let block_tuple: Option<UserBlockTuple> = self.manager
+            .read("user_block_get", &(user_id.to_string(), blocked_user_id.to_string())).await?
+            .decode_single()?;
+
+        block_tuple.map(user_block_from_tuple).transpose()
+    }
+
+    async fn get_blocks_by_user_id(&self, user_id: &Uuid) -> Result<Vec<UserBlock>, io::Error> {
+        let block_tuples: Vec<UserBlockTuple> = self.manager
+            .read("user_block_get_by_user_id", &(user_id.to_string(),)).await?
+            .decode_single()?;
+
+        block_tuples.into_iter().map(user_block_from_tuple).collect()
+    }
+
+    async fn get_blocks_by_blocked_user_id(&self, blocked_user_id: &Uuid) -> Result<Vec<UserBlock>, io::Error> {
+        let block_tuples: Vec<UserBlockTuple> = self.manager
+            .read("user_block_get_by_blocked_user_id", &(blocked_user_id.to_string(),)).await?
+            .decode_single()?;
+
+        block_tuples.into_iter().map(user_block_from_tuple).collect()
+    }
+
+    async fn create_block(&self, block: &UserBlock) -> Result<Uuid, io::Error> {
+        let block_tuple: UserBlockTuple = self.manager
+            .write("user_block_create", &(
+                block.get_id().to_string(),
+                block.get_user_id().to_string(),
+                block.get_blocked_user_id().to_string(),
+                block.get_time_created().and_utc().timestamp(),
+            )).await?
+            .decode_single()?;
+
+        tarantool::parse_uuid(block_tuple.0.as_str())
+    }
+
+    async fn delete_block(&self, user_id: &Uuid, blocked_user_id: &Uuid) -> Result<bool, io::Error> {
+        let is_deleted: bool = self.manager
+            .write("user_block_delete", &(user_id.to_string(), blocked_user_id.to_string())).await?
+            .decode_single()?;
+
+        Ok(is_deleted)
+    }
+
+    async fn delete_blocks_by_user_id(&self, user_id: &Uuid) -> Result<u64, io::Error> {
+        let deleted_count: u64 = self.manager
+            .write("user_block_delete_by_user_id", &(user_id.to_string(),)).await?
+            .decode_single()?;
+
+        Ok(deleted_count)
+    }
 }
@@ -1,11 +1,12 @@
 use futures::io;
-use rusty_tarantool::tarantool::{Client, ExecWithParamaters as _};
 use tonic::async_trait;
 use uuid::Uuid;
 
 use crate::session::Session;
 use crate::session_storage::SessionStorage;
-use crate::tarantool::TarantoolClientManager;
+use crate::tarantool::{self, TarantoolClientManager};
+
+type SessionTuple = (String, String, String, i64, i64);
 
 pub struct TarantoolSessionStorage {
     manager: TarantoolClientManager,
@@ -17,45 +18,79 @@ impl TarantoolSessionStorage {
             manager,
         }
     }
+}
 
-    pub fn get_client(&self) -> &Client {
-        self.manager.get_client()
-    }
-
-    pub async fn get_leader_client(&self) -> &Client {
-        self.manager.get_leader_client().await
-    }
+fn session_from_tuple(session_tuple: SessionTuple) -> Session {
+    Session::restore(
+        session_tuple.0,
+        session_tuple.1,
+        session_tuple.2,
+        tarantool::timestamp_to_datetime(session_tuple.3),
+        tarantool::timestamp_to_datetime(session_tuple.4),
+    )
 }
 
 #[async_trait]
 impl SessionStorage for TarantoolSessionStorage {
     async fn create(&self, session: &Session) -> Result<Uuid, io::Error> {
-        let session_tuple: (String, String, String) = self.get_leader_client().await
-            .prepare_fn_call("session_create")
-            .bind(session.get_id().to_string().as_str()).unwrap()
-            .bind(session.get_user_id().to_string().as_str()).unwrap()
-            .bind(session.get_data().to_string().as_str()).unwrap()
-            .execute().await.unwrap()
-            .decode_single().unwrap();
+        let session_tuple: SessionTuple = self.manager
+            .write("session_create", &(
+                session.get_id().to_string(),
+                session.get_user_id().to_string(),
+                session.get_data().to_string(),
+                session.get_time_created().and_utc().timestamp(),
+                session.get_time_updated().and_utc().timestamp(),
+            )).await?
+            .decode_single()?;
 
-        Ok(uuid::Uuid::parse_str(session_tuple.0.as_str()).unwrap())
+        tarantool::parse_uuid(session_tuple.0.as_str())
     }
 
     async fn get_by_id(&self, id: &str) -> Result<Option<Session>, io::Error> {
-        if let Ok(session_tuple) = self.get_client()
-            .prepare_fn_call("session_get_by_id")
-            .bind(id).unwrap()
-            .execute().await.unwrap()
-            .decode_single::<(String, String, String)>() {
-                Ok(Some(
-                    Session::new(
-                        String::from(session_tuple.0),
-                        String::from(session_tuple.1), 
-                        String::from(session_tuple.2),
-                    )
-                ))
-            } else {
-                Ok(None)
-            }
-    }
-}
\ No newline at end of file
+        let session_tuple: Option<SessionTuple> = self.manager
+            .read("session_get_by_id", &(id,)).await?
+            .decode_single()?;
+
+        Ok(session_tuple.map(session_from_tuple))
+    }
+
+    async fn get_by_user_id(&self, user_id: &Uuid) -> Result<Vec<Session>, io::Error> {
+        let session_tuples: Vec<SessionTuple> = self.manager
+            .read("session_get_by_user_id", &(user_id.to_string(),)).await?
+            .decode_single()?;
+
+        Ok(session_tuples.into_iter().map(session_from_tuple).collect())
+    }
+
+    async fn get_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Session>, io::Error> {
+        let session_tuples: Vec<SessionTuple> = self.manager
+            .read("session_get_batch", &(after_id.map(|id| id.to_string()), limit)).await?
+            .decode_single()?;
+
+        Ok(session_tuples.into_iter().map(session_from_tuple).collect())
+    }
+
+    async fn touch(&self, id: &Uuid, time_updated: &chrono::NaiveDateTime) -> Result<bool, io::Error> {
+        let is_touched: bool = self.manager
+            .write("session_touch", &(id.to_string(), time_updated.and_utc().timestamp())).await?
+            .decode_single()?;
+
+        Ok(is_touched)
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<bool, io::Error> {
+        let is_deleted: bool = self.manager
+            .write("session_delete", &(id.to_string(),)).await?
+            .decode_single()?;
+
+        Ok(is_deleted)
+    }
+
+    async fn delete_by_user_id(&self, user_id: &Uuid, except_id: Option<&Uuid>) -> Result<u64, io::Error> {
+        let deleted_count: u64 = self.manager
+            .write("session_delete_by_user_id", &(user_id.to_string(), except_id.map(|id| id.to_string()))).await?
+            .decode_single()?;
+
+        Ok(deleted_count)
+    }
+}
@@ -1,9 +1,8 @@
-use std::{fmt, vec};
+use std::fmt;
 use std::str::FromStr;
 use std::error::Error;
 use chrono::NaiveDate;
-use tokio_postgres::{Error as PostgresError, GenericClient, Row, RowStream};
-use futures_util::{pin_mut, TryStreamExt};
+use tokio_postgres::{error::SqlState, Error as PostgresError, GenericClient, Row};
 use uuid::Uuid;
 use serde::{Deserialize, Serialize};
 use unicode_segmentation::UnicodeSegmentation;
@@ -16,7 +15,46 @@ use argon2::{
     Argon2
 };
 
-pub const MAX_COUNT: usize = 16;
+pub const SEARCH_DEFAULT_RESULTS_PER_PAGE: usize = 20;
+pub const SEARCH_MAX_RESULTS_PER_PAGE: usize = 100;
+
+// prefix: full-text search by word prefixes over the tsvector columns,
+// fuzzy: pg_trgm similarity, tolerates typos and also matches name prefixes,
+// substring: case-insensitive substring match
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UserSearchMode {
+    Prefix,
+    Fuzzy,
+    Substring,
+}
+
+impl FromStr for UserSearchMode {
+    type Err = UserDataError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "fuzzy" => Ok(UserSearchMode::Fuzzy),
+            "prefix" => Ok(UserSearchMode::Prefix),
+            "substring" => Ok(UserSearchMode::Substring),
+            _ => Err(UserDataError::new("search mode should be one of: fuzzy, prefix, substring")),
+        }
+    }
+}
+
+// Every word becomes a prefix lexeme, tsquery operators from the input are dropped
+fn to_prefix_tsquery(value: &str) -> String {
+    value
+        .split_whitespace()
+        .map(|word| word.chars().filter(|c| c.is_alphanumeric()).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .map(|word| word + ":*")
+        .collect::<Vec<_>>()
+        .join(" & ")
+}
+
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct User {
@@ -26,6 +64,9 @@ pub struct User {
     birthdate: chrono::NaiveDate,
     biography: String,
     city: String,
+    // login may hold an email address, so it is never exposed in profiles
+    #[serde(skip_serializing)]
+    login: Option<String>,
 }
 
 impl From<Row> for User {
@@ -38,7 +79,94 @@ impl From<Row> for User {
             birthdate,
             biography: row.get(4),
             city: row.get(5),
+            login: row.get(6),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserSearchPage {
+    pub users: Vec<User>,
+    pub page_number: usize,
+    pub results_per_page: usize,
+    pub has_next_page: bool,
+}
+
+const NAME_MAX_LENGTH: usize = 32;
+const CITY_MAX_LENGTH: usize = 32;
+const BIOGRAPHY_MAX_LENGTH: usize = 2048;
+
+// Field limits shared by registration and profile updates
+fn check_length(field: &str, value: &str, max_length: usize) -> Result<String, UserDataError> {
+    if value.graphemes(true).count() <= max_length {
+        Ok(value.to_string())
+    } else {
+        Err(UserDataError::new(&(field.to_owned() + " is too long")))
+    }
+}
+
+fn parse_birthdate(value: &str) -> Result<NaiveDate, UserDataError> {
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(birthdate) => Ok(birthdate),
+        Err(e) => {
+            log::debug!("birthdate format is incorrect: {:?}", e);
+            Err(UserDataError::new("birthdate format is incorrect, should be %Y-%m-%d"))
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserFieldError {
+    field: &'static str,
+    message: String,
+}
+
+// Fields that are None stay unchanged
+#[derive(Debug, Default)]
+pub struct UserUpdate {
+    first_name: Option<String>,
+    second_name: Option<String>,
+    birthdate: Option<NaiveDate>,
+    biography: Option<String>,
+    city: Option<String>,
+}
+
+impl UserUpdate {
+    // Validates every field and reports all failures at once
+    pub fn new(
+        first_name: Option<&String>,
+        second_name: Option<&String>,
+        birthdate: Option<&String>,
+        biography: Option<&String>,
+        city: Option<&String>,
+    ) -> Result<UserUpdate, Vec<UserFieldError>> {
+        let mut errors = vec![];
+        let mut collect = |field: &'static str, result: Option<Result<String, UserDataError>>| match result {
+            Some(Ok(value)) => Some(value),
+            Some(Err(err)) => {
+                errors.push(UserFieldError { field, message: err.to_string() });
+                None
+            }
+            None => None,
+        };
+
+        let first_name = collect("first_name", first_name.map(|value| check_length("first_name", value, NAME_MAX_LENGTH)));
+        let second_name = collect("second_name", second_name.map(|value| check_length("second_name", value, NAME_MAX_LENGTH)));
+        let biography = collect("biography", biography.map(|value| check_length("biography", value, BIOGRAPHY_MAX_LENGTH)));
+        let city = collect("city", city.map(|value| check_length("city", value, CITY_MAX_LENGTH)));
+        let birthdate = match birthdate.map(|value| parse_birthdate(value)) {
+            Some(Ok(birthdate)) => Some(birthdate),
+            Some(Err(err)) => {
+                errors.push(UserFieldError { field: "birthdate", message: err.to_string() });
+                None
+            }
+            None => None,
+        };
+
+        if !errors.is_empty() {
+            return Err(errors);
         }
+        Ok(UserUpdate { first_name, second_name, birthdate, biography, city })
     }
 }
 
@@ -65,6 +193,39 @@ impl Error for UserDataError {
     }
 }
 
+// See migrations/0001_alter-users-add-login_up.sql
+const LOGIN_UNIQUE_INDEX_NAME: &str = "users_login_lower_idx";
+
+#[derive(Debug)]
+pub enum UserCreateError {
+    LoginTaken,
+    Postgres(PostgresError),
+}
+
+impl fmt::Display for UserCreateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UserCreateError::LoginTaken => write!(f, "login is already taken"),
+            UserCreateError::Postgres(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for UserCreateError {}
+
+impl From<PostgresError> for UserCreateError {
+    fn from(err: PostgresError) -> Self {
+        let is_login_taken = err.as_db_error().is_some_and(|db_err| {
+            db_err.code() == &SqlState::UNIQUE_VIOLATION && db_err.constraint() == Some(LOGIN_UNIQUE_INDEX_NAME)
+        });
+        if is_login_taken {
+            UserCreateError::LoginTaken
+        } else {
+            UserCreateError::Postgres(err)
+        }
+    }
+}
+
 impl User {
     pub fn id(&self) -> Uuid {
         self.id
@@ -90,108 +251,210 @@ impl User {
         &self.city
     }
 
+    pub fn login(&self) -> Option<&str> {
+        self.login.as_deref()
+    }
+
     pub fn new(
         first_name: &String,
         second_name: &String,
         birthdate: &String,
         biography: &String,
         city: &String,
+        login: &Option<String>,
     ) -> Result<User, UserDataError> {
         Ok(User {
             id: Uuid::new_v4(),
-            first_name: if first_name.graphemes(true).count() <= 32 { first_name.to_string() } else { return Err(UserDataError::new("first_name is too long")) },
-            second_name: if second_name.graphemes(true).count() <= 32 { second_name.to_string() } else { return Err(UserDataError::new("second_name is too long")) },
-            birthdate: match chrono::NaiveDate::parse_from_str(&birthdate, "%Y-%m-%d") {
-                Ok(birthdate) => birthdate,
-                Err(e) => {
-                    log::debug!("birthdate format is incorrect: {:?}", e);
-                    return Err(UserDataError::new("birthdate format is incorrect, should be %Y-%m-%d"))
-                }
+            first_name: check_length("first_name", first_name, NAME_MAX_LENGTH)?,
+            second_name: check_length("second_name", second_name, NAME_MAX_LENGTH)?,
+            birthdate: parse_birthdate(birthdate)?,
+            biography: check_length("biography", biography, BIOGRAPHY_MAX_LENGTH)?,
+            city: check_length("city", city, CITY_MAX_LENGTH)?,
+            login: match login {
+                Some(login) => { User::is_login_correct(login)?; Some(login.trim().to_string()) },
+                None => None,
             },
-            biography: if biography.graphemes(true).count() <= 2048 { biography.to_string() } else { return Err(UserDataError::new("biography is too long")) },
-            city: if city.graphemes(true).count() <= 32 { city.to_string() } else { return Err(UserDataError::new("city is too long")) },
         })
     }
 
-    pub async fn get_all<C: GenericClient>(client: &C) -> Result<Vec<User>, PostgresError> {
-        let stmt = client.prepare("SELECT id, first_name, second_name, birthdate, biography, city FROM users").await?;
-        let rows = client.query(&stmt, &[]).await?;
+    // Keyset pagination by id: pass the id of the last user of the previous page
+    pub async fn get_page<C: GenericClient>(
+        client: &C,
+        after_id: Option<&Uuid>,
+        city: Option<&str>,
+        birthdate_from: Option<&NaiveDate>,
+        birthdate_to: Option<&NaiveDate>,
+        limit: usize,
+    ) -> Result<Vec<User>, PostgresError> {
+        let stmt = client.prepare(
+            "SELECT id, first_name, second_name, birthdate, biography, city, login FROM users \
+            WHERE ($1::uuid IS NULL OR id > $1) \
+            AND ($2::text IS NULL OR city = $2) \
+            AND ($3::date IS NULL OR birthdate >= $3) \
+            AND ($4::date IS NULL OR birthdate <= $4) \
+            ORDER BY id LIMIT $5"
+        ).await?;
+        let rows = client.query(&stmt, &[&after_id, &city, &birthdate_from, &birthdate_to, &(limit as i64)]).await?;
         Ok(rows.into_iter().map(User::from).collect())
     }
 
     pub async fn get_by_id<C: GenericClient>(client: &C, id: &String) -> Result<User, PostgresError> {
-        let stmt = client.prepare("SELECT id, first_name, second_name, birthdate, biography, city FROM users WHERE id = $1").await?;
+        let stmt = client.prepare("SELECT id, first_name, second_name, birthdate, biography, city, login FROM users WHERE id = $1").await?;
         let row = client.query_one(&stmt, &[&Uuid::from_str(&id).unwrap()]).await?;
         Ok(User::from(row))
     }
 
-    pub async fn search_by_ids<C: GenericClient>(client: &C, ids: &Vec<String>) -> Result<Vec<User>, PostgresError> {
-        let arr_params = ids.iter().enumerate().map(|(i, _)| "$".to_owned() + (i+1).to_string().as_str());
-        let string_params = arr_params.reduce(|acc, x| acc + "," + &x).unwrap();
-        let str_params: &str = string_params.as_str();
-
+    // One statement for any number of ids, the order of the result is unspecified
+    pub async fn get_by_ids<C: GenericClient>(client: &C, ids: &[Uuid]) -> Result<Vec<User>, PostgresError> {
         let stmt = client.prepare(
-            ("SELECT id, first_name, second_name, birthdate, biography, city FROM users WHERE id::text IN (".to_owned() + &str_params + ")").as_str()
+            "SELECT id, first_name, second_name, birthdate, biography, city, login FROM users WHERE id = ANY($1::uuid[])"
         ).await?;
+        let rows = client.query(&stmt, &[&ids]).await?;
+        Ok(rows.into_iter().map(User::from).collect())
+    }
 
-        let rows_stream: RowStream = client.query_raw(&stmt, ids).await?.try_into().unwrap();
-
-        pin_mut!(rows_stream);
-        let mut users: Vec<User> = vec![];
-        while let Some(row) = rows_stream.try_next().await? {
-            users.push(User::from(row));
-        }
-        Ok(users)
+    // Keyset page over the given ids ordered by names, after_id is the last user of the previous page
+    pub async fn get_page_by_ids<C: GenericClient>(
+        client: &C,
+        ids: &[Uuid],
+        name_prefix: &str,
+        after_id: Option<&Uuid>,
+        limit: usize,
+    ) -> Result<Vec<User>, PostgresError> {
+        let name_pattern = escape_like(name_prefix.trim()) + "%";
+        let stmt = client.prepare(
+            "SELECT id, first_name, second_name, birthdate, biography, city, login FROM users \
+            WHERE id = ANY($1::uuid[]) \
+            AND (first_name ILIKE $2 OR second_name ILIKE $2) \
+            AND ($3::uuid IS NULL OR (second_name, first_name, id) > (SELECT second_name, first_name, id FROM users WHERE id = $3)) \
+            ORDER BY second_name, first_name, id \
+            LIMIT $4"
+        ).await?;
+        let rows = client.query(&stmt, &[&ids, &name_pattern, &after_id, &(limit as i64)]).await?;
+        Ok(rows.into_iter().map(User::from).collect())
     }
 
+    // Ranked by ts_rank over both names plus trigram similarity of each name, then by id for a stable order
     pub async fn search_by_first_name_and_last_name<C: GenericClient>(
         client: &C,
-        first_name: &String,
-        second_name: &String,
-        use_fast_search: bool
-    // ) -> Result<[User; MAX_COUNT], PostgresError> {
+        first_name: &str,
+        second_name: &str,
+        mode: UserSearchMode,
+        offset: usize,
+        limit: usize,
     ) -> Result<Vec<User>, PostgresError> {
-        let rows: Result<Vec<Row>, _>;
-        // let arr: Result<[Row; MAX_COUNT], _>;
-        if use_fast_search {
-            let stmt = client.prepare(
-                // "SELECT id, first_name, second_name, birthdate, biography, city FROM users WHERE (to_tsvector('russian', first_name) @@ to_tsquery('russian', $1)) AND (to_tsvector('russian', second_name) @@ to_tsquery('russian', $2)) ORDER BY id"
-                &("SELECT id, first_name, second_name, birthdate, biography, city FROM users WHERE textsearchable_first_name @@ to_tsquery('russian', $1) AND textsearchable_second_name @@ to_tsquery('russian', $2) ORDER BY id LIMIT ".to_owned() + MAX_COUNT.to_string().as_str())
-            ).await?;
-            rows = client.query(
-                &stmt,
-                &[&(first_name.to_owned() + ":*"), &(second_name.to_owned() + ":*")]
-            ).await;
-        } else {
-            let stmt = client.prepare(
-                &("SELECT id, first_name, second_name, birthdate, biography, city FROM users WHERE first_name ILIKE $1 AND second_name ILIKE $2 ORDER BY id LIMIT ".to_owned() + MAX_COUNT.to_string().as_str())
-            ).await?;
-            rows = client.query(
-                &stmt,
-                &[&("%".to_owned() + &first_name + "%"), &("%".to_owned() + &second_name.to_owned() + "%")]
-            ).await;
-        }
-        // arr = rows.expect("SQL query execution failed").try_into();
-        // Ok(arr.expect("Unable to create User array").map(User::from))
-        Ok(rows.expect("SQL query execution failed").into_iter().map(User::from).collect())
+        let first_name = first_name.trim();
+        let second_name = second_name.trim();
+
+        let (condition, first_name_pattern, second_name_pattern) = match mode {
+            UserSearchMode::Prefix => (
+                "($1 = '' OR textsearchable_first_name @@ to_tsquery('russian', $3)) \
+                AND ($2 = '' OR textsearchable_second_name @@ to_tsquery('russian', $4))",
+                to_prefix_tsquery(first_name),
+                to_prefix_tsquery(second_name),
+            ),
+            UserSearchMode::Fuzzy => (
+                "($1 = '' OR first_name % $1 OR first_name ILIKE $3) \
+                AND ($2 = '' OR second_name % $2 OR second_name ILIKE $4)",
+                escape_like(first_name) + "%",
+                escape_like(second_name) + "%",
+            ),
+            UserSearchMode::Substring => (
+                "first_name ILIKE $3 AND second_name ILIKE $4",
+                "%".to_owned() + &escape_like(first_name) + "%",
+                "%".to_owned() + &escape_like(second_name) + "%",
+            ),
+        };
+
+        let stmt = client.prepare(
+            &("SELECT id, first_name, second_name, birthdate, biography, city, login FROM users WHERE ".to_owned()
+                + condition
+                + " ORDER BY ts_rank(textsearchable_names, plainto_tsquery('russian', $1 || ' ' || $2)) \
+                + similarity(first_name, $1) + similarity(second_name, $2) DESC, id \
+                LIMIT $5 OFFSET $6")
+        ).await?;
+        let rows = client.query(
+            &stmt,
+            &[&first_name, &second_name, &first_name_pattern, &second_name_pattern, &(limit as i64), &(offset as i64)]
+        ).await?;
+        Ok(rows.into_iter().map(User::from).collect())
     }
 
-    pub async fn create<C: GenericClient>(client: &C, user: &User, password: &String) -> Result<Uuid, PostgresError> {
+    // page_number starts at 1, zero values fall back to the first page and the default page size
+    pub async fn search_page<C: GenericClient>(
+        client: &C,
+        first_name: &str,
+        second_name: &str,
+        mode: UserSearchMode,
+        page_number: usize,
+        results_per_page: usize,
+    ) -> Result<UserSearchPage, PostgresError> {
+        let page_number = page_number.max(1);
+        let results_per_page = match results_per_page {
+            0 => SEARCH_DEFAULT_RESULTS_PER_PAGE,
+            value => value.min(SEARCH_MAX_RESULTS_PER_PAGE),
+        };
+
+        let mut users = User::search_by_first_name_and_last_name(
+            client,
+            first_name,
+            second_name,
+            mode,
+            (page_number - 1) * results_per_page,
+            results_per_page + 1,
+        ).await?;
+        let has_next_page = results_per_page < users.len();
+        users.truncate(results_per_page);
+
+        Ok(UserSearchPage { users, page_number, results_per_page, has_next_page })
+    }
+
+    pub async fn create<C: GenericClient>(client: &C, user: &User, password: &String) -> Result<Uuid, UserCreateError> {
         let id = if "" == user.id.to_string() { Uuid::new_v4() } else { user.id };
+
+        if let Some(login) = &user.login {
+            if User::get_id_by_login(client, login).await?.is_some() {
+                return Err(UserCreateError::LoginTaken);
+            }
+        }
+
         let (password_hash, salt) = User::encrypt_password(&password);
 
         let stmt = client.prepare(
-            "INSERT INTO users (id, first_name, second_name, birthdate, biography, city, password_hash, salt) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+            "INSERT INTO users (id, first_name, second_name, birthdate, biography, city, login, password_hash, salt) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"
         ).await?;
 
         client.execute(
             &stmt,
-            &[&id, &user.first_name, &user.second_name, &user.birthdate, &user.biography, &user.city, &password_hash, &salt]
+            &[&id, &user.first_name, &user.second_name, &user.birthdate, &user.biography, &user.city, &user.login, &password_hash, &salt]
         ).await?;
 
         Ok(id)
     }
 
+    pub async fn get_id_by_login<C: GenericClient>(client: &C, login: &str) -> Result<Option<Uuid>, PostgresError> {
+        let stmt = client.prepare("SELECT id FROM users WHERE lower(login) = lower($1)").await?;
+        let row = client.query_opt(&stmt, &[&login.trim()]).await?;
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    pub fn is_login_correct(login: &String) -> Result<bool, UserDataError> {
+        let login = login.trim();
+        let login_length = login.graphemes(true).count();
+        if 254 < login_length {
+            return Err(UserDataError::new("login is too long"));
+        } else if login_length < 3 {
+            return Err(UserDataError::new("login is too short"));
+        }
+        if !login.chars().all(|c| c.is_alphanumeric() || "._-+@".contains(c)) {
+            return Err(UserDataError::new("login contains forbidden characters"));
+        }
+        if Uuid::parse_str(login).is_ok() {
+            return Err(UserDataError::new("login must not be a user id"));
+        }
+        Ok(true)
+    }
+
     pub fn is_password_correct(password: &String) -> Result<bool, UserDataError> {
         let password_length = password.graphemes(true).count();
         if 32 < password_length {
@@ -203,8 +466,44 @@ impl User {
     }
 
     pub async fn authenticate<C: GenericClient>(client: &C, id: &String, password: &String) -> bool {
-        let (password_hash, salt) = User::fetch_password_hash_and_salt(client, &id).await.unwrap();
-        User::check_password(password, &password_hash)
+        if Uuid::from_str(&id).is_err() {
+            return false;
+        }
+        match User::fetch_password_hash_and_salt(client, &id).await {
+            Ok((password_hash, _salt)) => User::check_password(password, &password_hash),
+            Err(_) => false,
+        }
+    }
+
+    // Returns the updated profile, None when the user does not exist
+    pub async fn update<C: GenericClient>(client: &C, id: &Uuid, update: &UserUpdate) -> Result<Option<User>, PostgresError> {
+        let stmt = client.prepare(
+            "UPDATE users SET \
+            first_name = COALESCE($2, first_name), \
+            second_name = COALESCE($3, second_name), \
+            birthdate = COALESCE($4, birthdate), \
+            biography = COALESCE($5, biography), \
+            city = COALESCE($6, city) \
+            WHERE id = $1 \
+            RETURNING id, first_name, second_name, birthdate, biography, city, login"
+        ).await?;
+        let row = client.query_opt(
+            &stmt,
+            &[id, &update.first_name, &update.second_name, &update.birthdate, &update.biography, &update.city]
+        ).await?;
+        Ok(row.map(User::from))
+    }
+
+    pub async fn update_password<C: GenericClient>(client: &C, id: &Uuid, password: &String) -> Result<bool, PostgresError> {
+        let (password_hash, salt) = User::encrypt_password(&password);
+
+        let stmt = client.prepare(
+            "UPDATE users SET password_hash = $2, salt = $3 WHERE id = $1"
+        ).await?;
+
+        let rows_count = client.execute(&stmt, &[id, &password_hash, &salt]).await?;
+
+        Ok(0 < rows_count)
     }
 
     async fn fetch_password_hash_and_salt<C: GenericClient>(client: &C, id: &String) -> Result<(String, String), PostgresError> {
@@ -213,7 +512,7 @@ impl User {
         Ok((row.get(0), row.get(1)))
     }
 
-    fn encrypt_password(password: &String) -> (String, String) {
+    pub fn encrypt_password(password: &String) -> (String, String) {
         let salt = SaltString::generate(&mut OsRng);
         let argon2 = Argon2::default();
         (
@@ -222,8 +521,56 @@ impl User {
         )
     }
 
-    fn check_password(password: &String, password_hash: &String) -> bool {
+    pub fn check_password(password: &String, password_hash: &String) -> bool {
         let parsed_hash = PasswordHash::new(&password_hash).unwrap();
         Argon2::default().verify_password(password.as_ref(), &parsed_hash).is_ok()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_tsquery_joins_words() {
+        assert_eq!(to_prefix_tsquery("  Ivan   Petr "), "Ivan:* & Petr:*");
+        assert_eq!(to_prefix_tsquery("ivan:* | !petr & ("), "ivan:* & petr:*");
+        assert_eq!(to_prefix_tsquery(" & | "), "");
+    }
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like("50%_off\\"), "50\\%\\_off\\\\");
+        assert_eq!(escape_like("Ivan"), "Ivan");
+    }
+
+    #[test]
+    fn user_update_reports_every_field_error() {
+        let long_name = "a".repeat(NAME_MAX_LENGTH + 1);
+        let birthdate = "01.02.2000".to_string();
+        let city = "Moscow".to_string();
+
+        let errors = UserUpdate::new(Some(&long_name), None, Some(&birthdate), None, Some(&city)).unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|error| error.field).collect();
+        assert_eq!(fields, vec!["first_name", "birthdate"]);
+    }
+
+    #[test]
+    fn user_update_keeps_missing_fields_unchanged() {
+        let birthdate = "2000-02-01".to_string();
+
+        let update = UserUpdate::new(None, None, Some(&birthdate), None, None).unwrap();
+        assert_eq!(update.first_name, None);
+        assert_eq!(update.birthdate, NaiveDate::from_ymd_opt(2000, 2, 1));
+    }
+
+    #[test]
+    fn login_is_checked() {
+        for login in ["ivan", " ivan.petrov+news@example.com "] {
+            assert!(User::is_login_correct(&login.to_string()).is_ok(), "{}", login);
+        }
+        for login in ["iv", "ivan petrov", "ivan#1", Uuid::new_v4().to_string().as_str(), "a".repeat(255).as_str()] {
+            assert!(User::is_login_correct(&login.to_string()).is_err(), "{}", login);
+        }
+    }
+}
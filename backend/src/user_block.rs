@@ -0,0 +1,106 @@
+use deadpool_redis::Connection;
+use futures::io;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::friend::{self, Friend};
+use crate::friend_request::{FriendRequest, FriendRequestStatus};
+use crate::friend_suggestion;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserBlock {
+    id: Uuid,
+    user_id: Uuid,
+    blocked_user_id: Uuid,
+    time_created: chrono::NaiveDateTime,
+}
+
+impl UserBlock {
+    pub fn new(user_id: Uuid, blocked_user_id: Uuid) -> UserBlock {
+        UserBlock::restore(Uuid::new_v4(), user_id, blocked_user_id, chrono::Utc::now().naive_utc())
+    }
+
+    pub fn restore(id: Uuid, user_id: Uuid, blocked_user_id: Uuid, time_created: chrono::NaiveDateTime) -> UserBlock {
+        UserBlock {
+            id,
+            user_id,
+            blocked_user_id,
+            time_created,
+        }
+    }
+
+    pub fn get_id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn get_user_id(&self) -> Uuid {
+        self.user_id
+    }
+
+    pub fn get_blocked_user_id(&self) -> Uuid {
+        self.blocked_user_id
+    }
+
+    pub fn get_time_created(&self) -> &chrono::NaiveDateTime {
+        &self.time_created
+    }
+
+    pub async fn get_by_user_id(user_id: &Uuid) -> Result<Vec<UserBlock>, io::Error> {
+        friend::get_storage().get_blocks_by_user_id(user_id).await
+    }
+
+    // A block works both ways: neither user sees the other one
+    pub async fn is_blocked_between(user_id: &Uuid, other_user_id: &Uuid) -> Result<bool, io::Error> {
+        Ok(friend::get_storage().get_block(user_id, other_user_id).await?.is_some()
+            || friend::get_storage().get_block(other_user_id, user_id).await?.is_some())
+    }
+
+    // Users blocked by the user together with the users who blocked them
+    pub async fn get_hidden_user_ids(user_id: &Uuid) -> Result<Vec<Uuid>, io::Error> {
+        let mut hidden_user_ids: Vec<Uuid> = friend::get_storage().get_blocks_by_user_id(user_id).await?
+            .iter()
+            .map(|block| block.get_blocked_user_id())
+            .collect();
+        hidden_user_ids.extend(friend::get_storage().get_blocks_by_blocked_user_id(user_id).await?
+            .iter()
+            .map(|block| block.get_user_id()));
+
+        Ok(hidden_user_ids)
+    }
+
+    // Friend records in both directions and pending requests between the users are removed along with the block
+    pub async fn block(block: &UserBlock, redis_connection: &mut Connection) -> Result<(), io::Error> {
+        friend::get_storage().create_block(block).await?;
+
+        for (user_id, friend_id) in [(block.user_id, block.blocked_user_id), (block.blocked_user_id, block.user_id)] {
+            if let Some(friend) = Friend::get_by_user_id_and_friend_id(&user_id, &friend_id).await? {
+                Friend::delete(&friend, redis_connection).await?;
+            }
+        }
+
+        for (from_user_id, to_user_id, status) in [
+            (block.user_id, block.blocked_user_id, FriendRequestStatus::Cancelled),
+            (block.blocked_user_id, block.user_id, FriendRequestStatus::Declined),
+        ] {
+            if let Some(mut request) = FriendRequest::get_pending(&from_user_id, &to_user_id).await? {
+                request.set_status(status);
+                FriendRequest::resolve(&request).await?;
+            }
+        }
+
+        friend_suggestion::invalidate(redis_connection, &[block.user_id, block.blocked_user_id]).await
+    }
+
+    pub async fn unblock(user_id: &Uuid, blocked_user_id: &Uuid, redis_connection: &mut Connection) -> Result<bool, io::Error> {
+        let is_deleted = friend::get_storage().delete_block(user_id, blocked_user_id).await?;
+        if is_deleted {
+            friend_suggestion::invalidate(redis_connection, &[*user_id, *blocked_user_id]).await?;
+        }
+
+        Ok(is_deleted)
+    }
+
+    pub async fn delete_by_user_id(user_id: &Uuid) -> Result<u64, io::Error> {
+        friend::get_storage().delete_blocks_by_user_id(user_id).await
+    }
+}
@@ -0,0 +1,156 @@
+use std::io::Write as _;
+use futures::io;
+use lazy_static::lazy_static;
+use tokio_postgres::{Error as PostgresError, GenericClient};
+use uuid::Uuid;
+
+use crate::access_token;
+use crate::friend::Friend;
+use crate::post::Post;
+use crate::postgres;
+use crate::session::Session;
+use crate::user::User;
+
+lazy_static! {
+    pub static ref USER_EXPORT_INLINE_MAX_POSTS: i64 = std::env::var("USER_EXPORT_INLINE_MAX_POSTS").unwrap_or_else(|_| "1000".to_string()).parse::<i64>().unwrap_or(1000);
+    pub static ref USER_EXPORT_TTL_SECONDS: i64 = std::env::var("USER_EXPORT_TTL_SECONDS").unwrap_or_else(|_| "86400".to_string()).parse::<i64>().unwrap_or(86400);
+    pub static ref USER_EXPORT_BUILD_TIMEOUT_SECONDS: i64 = std::env::var("USER_EXPORT_BUILD_TIMEOUT_SECONDS").unwrap_or_else(|_| "3600".to_string()).parse::<i64>().unwrap_or(3600);
+}
+
+pub enum UserExportState {
+    Ready(Vec<u8>),
+    Pending,
+}
+
+// Returns a finished archive, builds small ones in place and hands large ones over to a background task.
+// Archives are kept in Postgres for USER_EXPORT_TTL_SECONDS, so any instance can serve them
+pub async fn get_or_start<C: GenericClient>(client: &C, user_id: &Uuid) -> Result<UserExportState, io::Error> {
+    client.execute(
+        "DELETE FROM user_exports WHERE user_id = $1 AND time_expires <= NOW()",
+        &[user_id],
+    ).await.map_err(io::Error::other)?;
+
+    let stmt = client.prepare(
+        "SELECT archive, error, time_created > NOW() - make_interval(secs => $2) FROM user_exports \
+        WHERE user_id = $1 ORDER BY time_created DESC LIMIT 1"
+    ).await.map_err(io::Error::other)?;
+    let latest = client.query_opt(&stmt, &[user_id, &(*USER_EXPORT_BUILD_TIMEOUT_SECONDS as f64)]).await.map_err(io::Error::other)?;
+    if let Some(row) = latest {
+        let archive: Option<Vec<u8>> = row.get(0);
+        let error: Option<String> = row.get(1);
+        let is_building: bool = row.get(2);
+        match (archive, error) {
+            (Some(archive), _) => return Ok(UserExportState::Ready(archive)),
+            (None, None) if is_building => return Ok(UserExportState::Pending),
+            (None, error) => log::debug!("restarting export of user '{}', previous one failed: {:?}", user_id, error),
+        }
+    }
+
+    let stmt = client.prepare("SELECT COUNT(*) FROM posts WHERE user_id = $1").await.map_err(io::Error::other)?;
+    let posts_count: i64 = client.query_one(&stmt, &[user_id]).await.map_err(io::Error::other)?.get(0);
+    if posts_count <= *USER_EXPORT_INLINE_MAX_POSTS {
+        return Ok(UserExportState::Ready(build_archive(client, user_id).await?));
+    }
+
+    let export_id = Uuid::new_v4();
+    client.execute(
+        "INSERT INTO user_exports (id, user_id, time_expires) VALUES ($1, $2, NOW() + make_interval(secs => $3))",
+        &[&export_id, user_id, &(*USER_EXPORT_TTL_SECONDS as f64)],
+    ).await.map_err(io::Error::other)?;
+
+    let user_id = *user_id;
+    tokio::spawn(async move {
+        if let Err(err) = build_in_background(&export_id, &user_id).await {
+            log::warn!("unable to export user '{}': {:?}", user_id, err);
+        }
+    });
+
+    Ok(UserExportState::Pending)
+}
+
+async fn build_in_background(export_id: &Uuid, user_id: &Uuid) -> Result<(), PostgresError> {
+    let client = match postgres::get_master_pool_ref().get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to get postgres client: {:?}", err);
+            return Ok(());
+        }
+    };
+
+    match build_archive(&**client, user_id).await {
+        Ok(archive) => {
+            client.execute(
+                "UPDATE user_exports SET archive = $2, time_finished = NOW() WHERE id = $1",
+                &[export_id, &archive],
+            ).await?;
+            log::info!("export of user '{}' is ready", user_id);
+        }
+        Err(err) => {
+            client.execute(
+                "UPDATE user_exports SET error = $2, time_finished = NOW() WHERE id = $1",
+                &[export_id, &err.to_string()],
+            ).await?;
+            log::warn!("export of user '{}' failed: {:?}", user_id, err);
+        }
+    }
+    Ok(())
+}
+
+async fn build_archive<C: GenericClient>(client: &C, user_id: &Uuid) -> Result<Vec<u8>, io::Error> {
+    let user = User::get_by_id(client, &user_id.to_string()).await.map_err(io::Error::other)?;
+    let posts = Post::get_by_user_id(client, user_id).await.map_err(io::Error::other)?;
+    let friends = Friend::get_by_user_id(user_id).await?;
+    // Session ids are bearer tokens, only the metadata shown by /user/sessions is exported
+    let sessions: Vec<serde_json::Value> = Session::get_by_user_id(user_id).await?
+        .iter()
+        .map(|session| serde_json::json!({
+            "user_agent": session.get_data()["user_agent"].as_str(),
+            "ip": session.get_data()["ip"].as_str(),
+            "time_created": session.get_time_created(),
+            "time_last_seen": session.get_time_updated(),
+        }))
+        .collect();
+    let dialogs = fetch_messages(std::env::var("DIALOGS_SERVICE_URL").unwrap_or_else(|_| String::from("dialogs:8001")), user_id).await?;
+    let unread = fetch_messages(std::env::var("UNREAD_SERVICE_URL").unwrap_or_else(|_| String::from("unread:8001")), user_id).await?;
+
+    // login is never serialized with the profile
+    let files = [
+        ("profile.json", serde_json::json!({ "user": user, "login": user.login() })),
+        ("posts.json", serde_json::json!(posts)),
+        ("friends.json", serde_json::json!(friends)),
+        ("sessions.json", serde_json::json!(sessions)),
+        ("dialogs.json", dialogs),
+        ("unread.json", unread),
+    ];
+
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (name, content) in files.iter() {
+        writer.start_file(*name, options).map_err(io::Error::other)?;
+        writer.write_all(&serde_json::to_vec_pretty(content)?)?;
+    }
+    Ok(writer.finish().map_err(io::Error::other)?.into_inner())
+}
+
+// The services hand out the history only for a signed token, without ACCESS_TOKEN_SECRET it is exported as null
+async fn fetch_messages(service_url: String, user_id: &Uuid) -> Result<serde_json::Value, io::Error> {
+    if access_token::ACCESS_TOKEN_SECRET.is_none() {
+        log::warn!("messages of user '{}' are not exported from '{}': ACCESS_TOKEN_SECRET is not set", user_id, service_url);
+        return Ok(serde_json::Value::Null);
+    }
+
+    let response = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .map_err(io::Error::other)?
+        .post(service_url + "/user/messages")
+        .headers(access_token::get_headers_for_user(user_id))
+        .send()
+        .await
+        .map_err(io::Error::other)?;
+
+    if !response.status().is_success() {
+        return Err(io::Error::other(format!("service responded with {}", response.status())));
+    }
+    response.json::<serde_json::Value>().await.map_err(io::Error::other)
+}
@@ -1 +1,3 @@
 tonic::include_proto!("user_search");
+
+pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("user_search_descriptor");
@@ -0,0 +1,59 @@
+local function define_function(name, body)
+    box.schema.func.drop(name, { if_exists = true })
+    box.schema.func.create(name, { body = body })
+end
+
+box.schema.space.create('sessions', { if_not_exists = true })
+box.space.sessions:create_index('primary', { type = "HASH", unique = true, parts = { 1, 'string' }, if_not_exists = true })
+box.space.sessions:create_index('user_id', { type = "TREE", unique = false, parts = { 2, 'string' }, if_not_exists = true })
+
+-- Sessions written by the former app.lua have no timestamps, they restart their expiry from now
+local legacy_sessions = {}
+for _, session in box.space.sessions:pairs() do
+    if #session < 5 then
+        table.insert(legacy_sessions, session)
+    end
+end
+local now = os.time()
+for _, session in ipairs(legacy_sessions) do
+    box.space.sessions:replace{session[1], session[2], session[3], now, now}
+end
+
+-- async fn create(&self, session: &Session) -> Result<Uuid, Error>;
+define_function('session_create', [[function(id, user_id, data, time_created, time_updated)
+    return box.space.sessions:insert{id, user_id, data, time_created, time_updated}
+end]])
+
+-- async fn get_by_id(&self, id: &str) -> Result<Option<Session>, Error>;
+define_function('session_get_by_id', [[function(id)
+    return box.space.sessions.index.primary:get(id)
+end]])
+
+-- async fn get_by_user_id(&self, user_id: &Uuid) -> Result<Vec<Session>, Error>;
+define_function('session_get_by_user_id', [[function(user_id)
+    return box.space.sessions.index.user_id:select(user_id)
+end]])
+
+-- async fn touch(&self, id: &Uuid, time_updated: &chrono::NaiveDateTime) -> Result<bool, Error>;
+define_function('session_touch', [[function(id, time_updated)
+    return box.space.sessions:update(id, {{ '=', 5, time_updated }}) ~= nil
+end]])
+
+-- async fn delete(&self, id: &Uuid) -> Result<bool, Error>;
+define_function('session_delete', [[function(id)
+    return box.space.sessions:delete(id) ~= nil
+end]])
+
+-- async fn delete_by_user_id(&self, user_id: &Uuid, except_id: Option<&Uuid>) -> Result<u64, Error>;
+define_function('session_delete_by_user_id', [[function(user_id, except_id)
+    local ids = {}
+    for _, session in box.space.sessions.index.user_id:pairs(user_id) do
+        if session[1] ~= except_id then
+            table.insert(ids, session[1])
+        end
+    end
+    for _, id in ipairs(ids) do
+        box.space.sessions:delete(id)
+    end
+    return #ids
+end]])
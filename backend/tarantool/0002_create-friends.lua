@@ -0,0 +1,54 @@
+local function define_function(name, body)
+    box.schema.func.drop(name, { if_exists = true })
+    box.schema.func.create(name, { body = body })
+end
+
+box.schema.space.create('friends', { if_not_exists = true })
+
+-- The former app.lua created these indexes as BITSET, they are rebuilt as TREE below
+for _, name in ipairs({ 'user_id', 'friend_id' }) do
+    local index = box.space.friends.index[name]
+    if index ~= nil and index.type ~= 'TREE' then
+        index:drop()
+    end
+end
+
+box.space.friends:create_index('primary', { type = "HASH", unique = true, parts = { 1, 'string' }, if_not_exists = true })
+box.space.friends:create_index('user_id', { type = "TREE", unique = false, parts = { 2, 'string' }, if_not_exists = true })
+box.space.friends:create_index('friend_id', { type = "TREE", unique = false, parts = { 3, 'string' }, if_not_exists = true })
+box.space.friends:create_index('user_id_friend_id', { type = "HASH", unique = true, parts = { 2, 'string', 3, 'string' }, if_not_exists = true })
+
+-- async fn get_by_id(&self, id: &Uuid) -> Result<Option<Friend>, Error>;
+define_function('friend_get_by_id', [[function(id)
+    return box.space.friends.index.primary:get(id)
+end]])
+
+-- async fn get_by_user_id(&self, user_id: &Uuid) -> Result<Vec<Friend>, Error>;
+define_function('friend_get_by_user_id', [[function(user_id)
+    return box.space.friends.index.user_id:select(user_id)
+end]])
+
+-- async fn get_by_friend_id(&self, friend_id: &Uuid) -> Result<Vec<Friend>, Error>;
+define_function('friend_get_by_friend_id', [[function(friend_id)
+    return box.space.friends.index.friend_id:select(friend_id)
+end]])
+
+-- async fn get_by_user_id_and_friend_id(&self, user_id: &Uuid, friend_id: &Uuid) -> Result<Option<Friend>, Error>;
+define_function('friend_get_by_user_id_and_friend_id', [[function(user_id, friend_id)
+    return box.space.friends.index.user_id_friend_id:get{user_id, friend_id}
+end]])
+
+-- async fn create(&self, friend: &Friend) -> Result<Uuid, Error>;
+define_function('friend_create', [[function(id, user_id, friend_id)
+    return box.space.friends:insert{id, user_id, friend_id}
+end]])
+
+-- async fn delete(&self, friend: &Friend) -> Result<bool, Error>;
+define_function('friend_delete', [[function(user_id, friend_id)
+    return box.space.friends.index.user_id_friend_id:delete{user_id, friend_id} ~= nil
+end]])
+
+-- async fn is_persistant(&self, friend: &Friend) -> Result<bool, Error>;
+define_function('friend_is_persistant', [[function(user_id, friend_id)
+    return box.space.friends.index.user_id_friend_id:get{user_id, friend_id} ~= nil
+end]])
@@ -0,0 +1,12 @@
+local function define_function(name, body)
+    box.schema.func.drop(name, { if_exists = true })
+    box.schema.func.create(name, { body = body })
+end
+
+define_function('get_leader_name', [[function()
+    return box.info.election.leader_name
+end]])
+
+define_function('is_leader', [[function()
+    return not box.info.ro
+end]])
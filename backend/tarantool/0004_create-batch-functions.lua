@@ -0,0 +1,23 @@
+local function define_function(name, body)
+    box.schema.func.drop(name, { if_exists = true })
+    box.schema.func.create(name, { body = body })
+end
+
+box.space.sessions:create_index('id_tree', { type = "TREE", unique = true, parts = { 1, 'string' }, if_not_exists = true })
+box.space.friends:create_index('id_tree', { type = "TREE", unique = true, parts = { 1, 'string' }, if_not_exists = true })
+
+-- async fn get_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Session>, Error>;
+define_function('session_get_batch', [[function(after_id, limit)
+    if after_id == nil then
+        return box.space.sessions.index.id_tree:select({}, { limit = limit })
+    end
+    return box.space.sessions.index.id_tree:select(after_id, { iterator = 'GT', limit = limit })
+end]])
+
+-- async fn get_batch(&self, after_id: Option<&Uuid>, limit: usize) -> Result<Vec<Friend>, Error>;
+define_function('friend_get_batch', [[function(after_id, limit)
+    if after_id == nil then
+        return box.space.friends.index.id_tree:select({}, { limit = limit })
+    end
+    return box.space.friends.index.id_tree:select(after_id, { iterator = 'GT', limit = limit })
+end]])
@@ -0,0 +1,94 @@
+local function define_function(name, body)
+    box.schema.func.drop(name, { if_exists = true })
+    box.schema.func.create(name, { body = body })
+end
+
+box.schema.space.create('friend_requests', { if_not_exists = true })
+box.space.friend_requests:create_index('primary', { type = "HASH", unique = true, parts = { 1, 'string' }, if_not_exists = true })
+box.space.friend_requests:create_index('from_user_id', { type = "TREE", unique = false, parts = { 2, 'string' }, if_not_exists = true })
+box.space.friend_requests:create_index('to_user_id', { type = "TREE", unique = false, parts = { 3, 'string' }, if_not_exists = true })
+box.space.friend_requests:create_index('from_user_id_to_user_id', { type = "TREE", unique = false, parts = { 2, 'string', 3, 'string' }, if_not_exists = true })
+
+define_function('friend_request_select_pending', [[function(index, key)
+    local requests = {}
+    for _, request in box.space.friend_requests.index[index]:pairs(key) do
+        if request[4] == 'pending' then
+            table.insert(requests, request)
+        end
+    end
+    table.sort(requests, function(left, right) return left[5] > right[5] end)
+    return requests
+end]])
+
+-- async fn get_request_by_id(&self, id: &Uuid) -> Result<Option<FriendRequest>, Error>;
+define_function('friend_request_get_by_id', [[function(id)
+    return box.space.friend_requests.index.primary:get(id)
+end]])
+
+-- async fn get_pending_request(&self, from_user_id: &Uuid, to_user_id: &Uuid) -> Result<Option<FriendRequest>, Error>;
+define_function('friend_request_get_pending', [[function(from_user_id, to_user_id)
+    return box.func.friend_request_select_pending:call({ 'from_user_id_to_user_id', { from_user_id, to_user_id } })[1]
+end]])
+
+-- async fn get_pending_requests_by_to_user_id(&self, to_user_id: &Uuid) -> Result<Vec<FriendRequest>, Error>;
+define_function('friend_request_get_pending_by_to_user_id', [[function(to_user_id)
+    return box.func.friend_request_select_pending:call({ 'to_user_id', to_user_id })
+end]])
+
+-- async fn get_pending_requests_by_from_user_id(&self, from_user_id: &Uuid) -> Result<Vec<FriendRequest>, Error>;
+define_function('friend_request_get_pending_by_from_user_id', [[function(from_user_id)
+    return box.func.friend_request_select_pending:call({ 'from_user_id', from_user_id })
+end]])
+
+-- async fn create_request(&self, request: &FriendRequest) -> Result<Uuid, Error>;
+define_function('friend_request_create', [[function(id, from_user_id, to_user_id, status, time_created, time_updated)
+    return box.atomic(function()
+        if box.func.friend_request_get_pending:call({ from_user_id, to_user_id }) ~= nil then
+            box.error({ reason = 'friend request is already pending' })
+        end
+        return box.space.friend_requests:insert{id, from_user_id, to_user_id, status, time_created, time_updated}
+    end)
+end]])
+
+-- async fn update_request_status(&self, request: &FriendRequest) -> Result<bool, Error>;
+define_function('friend_request_update_status', [[function(id, status, time_updated)
+    return box.atomic(function()
+        local request = box.space.friend_requests.index.primary:get(id)
+        if request == nil or request[4] ~= 'pending' then
+            return false
+        end
+        box.space.friend_requests:update(id, {{ '=', 4, status }, { '=', 6, time_updated }})
+        return true
+    end)
+end]])
+
+-- async fn accept_request(&self, request: &FriendRequest, friends: &[Friend]) -> Result<bool, Error>;
+define_function('friend_request_accept', [[function(id, status, time_updated, friends)
+    return box.atomic(function()
+        local request = box.space.friend_requests.index.primary:get(id)
+        if request == nil or request[4] ~= 'pending' then
+            return false
+        end
+        box.space.friend_requests:update(id, {{ '=', 4, status }, { '=', 6, time_updated }})
+        for _, friend in ipairs(friends) do
+            if box.space.friends.index.user_id_friend_id:get{friend[2], friend[3]} == nil then
+                box.space.friends:insert(friend)
+            end
+        end
+        return true
+    end)
+end]])
+
+-- async fn delete_requests_by_user_id(&self, user_id: &Uuid) -> Result<u64, Error>;
+define_function('friend_request_delete_by_user_id', [[function(user_id)
+    return box.atomic(function()
+        local count = 0
+        for _, index in ipairs({ 'from_user_id', 'to_user_id' }) do
+            for _, request in ipairs(box.space.friend_requests.index[index]:select(user_id)) do
+                box.space.friend_requests:delete(request[1])
+                count = count + 1
+            end
+        end
+        return count
+    end)
+end]])
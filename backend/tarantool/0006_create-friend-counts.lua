@@ -0,0 +1,81 @@
+local function define_function(name, body)
+    box.schema.func.drop(name, { if_exists = true })
+    box.schema.func.create(name, { body = body })
+end
+
+-- { user_id, followers_count, following_count }
+box.schema.space.create('friend_counts', { if_not_exists = true })
+box.space.friend_counts:create_index('primary', { type = "HASH", unique = true, parts = { 1, 'string' }, if_not_exists = true })
+
+define_function('friend_counts_change', [[function(user_id, friend_id, delta)
+    box.space.friend_counts:upsert({ user_id, 0, delta }, {{ '+', 3, delta }})
+    box.space.friend_counts:upsert({ friend_id, delta, 0 }, {{ '+', 2, delta }})
+end]])
+
+-- Counts of friends created before this version are recomputed from scratch and replaced,
+-- so running the script again (e.g. from another instance) doesn't add them twice
+box.atomic(function()
+    local counts = {}
+    for _, friend in box.space.friends:pairs() do
+        counts[friend[2]] = counts[friend[2]] or { 0, 0 }
+        counts[friend[3]] = counts[friend[3]] or { 0, 0 }
+        counts[friend[2]][2] = counts[friend[2]][2] + 1
+        counts[friend[3]][1] = counts[friend[3]][1] + 1
+    end
+    for _, user_counts in box.space.friend_counts:pairs() do
+        if counts[user_counts[1]] == nil then
+            counts[user_counts[1]] = { 0, 0 }
+        end
+    end
+    for user_id, user_counts in pairs(counts) do
+        box.space.friend_counts:replace{ user_id, user_counts[1], user_counts[2] }
+    end
+end)
+
+-- async fn get_counts(&self, user_id: &Uuid) -> Result<FriendCounts, Error>;
+define_function('friend_get_counts', [[function(user_id)
+    local counts = box.space.friend_counts.index.primary:get(user_id)
+    if counts == nil then
+        return { 0, 0 }
+    end
+    return { counts[2], counts[3] }
+end]])
+
+-- async fn create(&self, friend: &Friend) -> Result<Uuid, Error>;
+define_function('friend_create', [[function(id, user_id, friend_id)
+    return box.atomic(function()
+        local friend = box.space.friends:insert{id, user_id, friend_id}
+        box.func.friend_counts_change:call({ user_id, friend_id, 1 })
+        return friend
+    end)
+end]])
+
+-- async fn delete(&self, friend: &Friend) -> Result<bool, Error>;
+define_function('friend_delete', [[function(user_id, friend_id)
+    return box.atomic(function()
+        local friend = box.space.friends.index.user_id_friend_id:delete{user_id, friend_id}
+        if friend == nil then
+            return false
+        end
+        box.func.friend_counts_change:call({ user_id, friend_id, -1 })
+        return true
+    end)
+end]])
+
+-- async fn accept_request(&self, request: &FriendRequest, friends: &[Friend]) -> Result<bool, Error>;
+define_function('friend_request_accept', [[function(id, status, time_updated, friends)
+    return box.atomic(function()
+        local request = box.space.friend_requests.index.primary:get(id)
+        if request == nil or request[4] ~= 'pending' then
+            return false
+        end
+        box.space.friend_requests:update(id, {{ '=', 4, status }, { '=', 6, time_updated }})
+        for _, friend in ipairs(friends) do
+            if box.space.friends.index.user_id_friend_id:get{friend[2], friend[3]} == nil then
+                box.space.friends:insert(friend)
+                box.func.friend_counts_change:call({ friend[2], friend[3], 1 })
+            end
+        end
+        return true
+    end)
+end]])
@@ -0,0 +1,55 @@
+local function define_function(name, body)
+    box.schema.func.drop(name, { if_exists = true })
+    box.schema.func.create(name, { body = body })
+end
+
+box.schema.space.create('user_blocks', { if_not_exists = true })
+box.space.user_blocks:create_index('primary', { type = "HASH", unique = true, parts = { 1, 'string' }, if_not_exists = true })
+box.space.user_blocks:create_index('user_id', { type = "TREE", unique = false, parts = { 2, 'string' }, if_not_exists = true })
+box.space.user_blocks:create_index('blocked_user_id', { type = "TREE", unique = false, parts = { 3, 'string' }, if_not_exists = true })
+box.space.user_blocks:create_index('user_id_blocked_user_id', { type = "HASH", unique = true, parts = { 2, 'string', 3, 'string' }, if_not_exists = true })
+
+-- async fn get_block(&self, user_id: &Uuid, blocked_user_id: &Uuid) -> Result<Option<UserBlock>, Error>;
+define_function('user_block_get', [[function(user_id, blocked_user_id)
+    return box.space.user_blocks.index.user_id_blocked_user_id:get{user_id, blocked_user_id}
+end]])
+
+-- async fn get_blocks_by_user_id(&self, user_id: &Uuid) -> Result<Vec<UserBlock>, Error>;
+define_function('user_block_get_by_user_id', [[function(user_id)
+    return box.space.user_blocks.index.user_id:select(user_id)
+end]])
+
+-- async fn get_blocks_by_blocked_user_id(&self, blocked_user_id: &Uuid) -> Result<Vec<UserBlock>, Error>;
+define_function('user_block_get_by_blocked_user_id', [[function(blocked_user_id)
+    return box.space.user_blocks.index.blocked_user_id:select(blocked_user_id)
+end]])
+
+-- async fn create_block(&self, block: &UserBlock) -> Result<Uuid, Error>;
+define_function('user_block_create', [[function(id, user_id, blocked_user_id, time_created)
+    return box.atomic(function()
+        local block = box.space.user_blocks.index.user_id_blocked_user_id:get{user_id, blocked_user_id}
+        if block ~= nil then
+            return block
+        end
+        return box.space.user_blocks:insert{id, user_id, blocked_user_id, time_created}
+    end)
+end]])
+
+-- async fn delete_block(&self, user_id: &Uuid, blocked_user_id: &Uuid) -> Result<bool, Error>;
+define_function('user_block_delete', [[function(user_id, blocked_user_id)
+    return box.space.user_blocks.index.user_id_blocked_user_id:delete{user_id, blocked_user_id} ~= nil
+end]])
+
+-- async fn delete_blocks_by_user_id(&self, user_id: &Uuid) -> Result<u64, Error>;
+define_function('user_block_delete_by_user_id', [[function(user_id)
+    return box.atomic(function()
+        local count = 0
+        for _, index in ipairs({ 'user_id', 'blocked_user_id' }) do
+            for _, block in ipairs(box.space.user_blocks.index[index]:select(user_id)) do
+                box.space.user_blocks:delete(block[1])
+                count = count + 1
+            end
+        end
+        return count
+    end)
+end]])
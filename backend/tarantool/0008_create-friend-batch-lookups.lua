@@ -0,0 +1,26 @@
+local function define_function(name, body)
+    box.schema.func.drop(name, { if_exists = true })
+    box.schema.func.create(name, { body = body })
+end
+
+-- async fn get_by_user_ids(&self, user_ids: &[Uuid]) -> Result<Vec<Friend>, Error>;
+define_function('friend_get_by_user_ids', [[function(user_ids)
+    local friends = {}
+    for _, user_id in ipairs(user_ids) do
+        for _, friend in box.space.friends.index.user_id:pairs(user_id) do
+            table.insert(friends, friend)
+        end
+    end
+    return friends
+end]])
+
+-- async fn get_by_friend_ids(&self, friend_ids: &[Uuid]) -> Result<Vec<Friend>, Error>;
+define_function('friend_get_by_friend_ids', [[function(friend_ids)
+    local friends = {}
+    for _, friend_id in ipairs(friend_ids) do
+        for _, friend in box.space.friends.index.friend_id:pairs(friend_id) do
+            table.insert(friends, friend)
+        end
+    end
+    return friends
+end]])
@@ -0,0 +1 @@
+ALTER TABLE dialog_messages DROP COLUMN IF EXISTS time_created;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE dialog_messages ADD COLUMN IF NOT EXISTS time_created TIMESTAMP DEFAULT now();
\ No newline at end of file
@@ -0,0 +1,75 @@
+use actix_web::{dev::Payload, Error, FromRequest, HttpRequest};
+use actix_web_httpauth::extractors::bearer;
+use actix_web_httpauth::extractors::AuthenticationError;
+use actix_web_httpauth::headers::authorization::{Authorization, Bearer};
+use actix_web::http::header::Header;
+use futures::future::{ready, Ready};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+struct AccessTokenClaims {
+    sub: Uuid,
+}
+
+// With ACCESS_TOKEN_SECRET the acting user always comes from the token, without it the service
+// keeps trusting user ids passed in the request body
+pub struct AccessTokenUser {
+    user_id: Option<Uuid>,
+}
+
+impl AccessTokenUser {
+    pub fn get_user_id(&self) -> Option<Uuid> {
+        self.user_id
+    }
+
+    pub fn get_acting_user_id(&self, body_user_id: Option<&str>) -> Option<Uuid> {
+        match self.user_id {
+            Some(user_id) => Some(user_id),
+            None => body_user_id.and_then(|user_id| Uuid::parse_str(user_id).ok()),
+        }
+    }
+}
+
+fn get_secret() -> Option<String> {
+    std::env::var("ACCESS_TOKEN_SECRET").ok().filter(|secret| !secret.is_empty())
+}
+
+fn verify(req: &HttpRequest, secret: &str) -> Result<Uuid, Error> {
+    let config = req.app_data::<bearer::Config>().cloned().unwrap_or_default();
+
+    let authorization = match Authorization::<Bearer>::parse(req) {
+        Ok(authorization) => authorization,
+        Err(err) => {
+            log::debug!("unauthorized: access token is missing: {:?}", err);
+            return Err(AuthenticationError::from(config).into());
+        }
+    };
+
+    match decode::<AccessTokenClaims>(
+        authorization.as_ref().token(),
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &Validation::new(Algorithm::HS256),
+    ) {
+        Ok(token_data) => Ok(token_data.claims.sub),
+        Err(err) => {
+            log::debug!("unauthorized: access token is invalid: {:?}", err);
+            Err(AuthenticationError::from(config)
+                .with_error(bearer::Error::InvalidToken)
+                .into())
+        }
+    }
+}
+
+impl FromRequest for AccessTokenUser {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(match get_secret() {
+            Some(secret) => verify(req, &secret).map(|user_id| AccessTokenUser { user_id: Some(user_id) }),
+            None => Ok(AccessTokenUser { user_id: None }),
+        })
+    }
+}
@@ -14,6 +14,9 @@ impl Message {
     // pub fn get_id(&self) -> Uuid {
     //     self.id
     // }
+    pub fn is_participant(&self, user_id: &Uuid) -> bool {
+        self.sender_user_id == *user_id || self.receiver_user_id == *user_id
+    }
     pub async fn save<C: GenericClient>(
         pg_client: &C,
         message_id: Uuid,
@@ -93,6 +96,37 @@ impl Message {
 
         Ok(())
     }
+    pub async fn list_by_user_id<C: GenericClient>(
+        pg_client: &C,
+        user_id: Uuid,
+    ) -> Result<Vec<Message>, tokio_postgres::Error> {
+        let stmt = pg_client.prepare(
+            "SELECT id, sender_user_id, receiver_user_id, content FROM dialog_messages WHERE sender_user_id = $1 OR receiver_user_id = $1 ORDER BY time_created"
+        ).await?;
+        let rows = pg_client.query(&stmt, &[&user_id]).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Message {
+                id: row.get(0),
+                sender_user_id: row.get(1),
+                receiver_user_id: row.get(2),
+                content: row.get(3),
+            })
+            .collect::<Vec<Message>>())
+    }
+
+    // The other participant keeps the dialog history, the deleted user is replaced with the nil uuid
+    pub async fn remove_by_user_id<C: GenericClient>(
+        pg_client: &C,
+        user_id: Uuid,
+    ) -> Result<u64, tokio_postgres::Error> {
+        let stmt = pg_client.prepare(
+            "UPDATE dialog_messages SET sender_user_id = CASE WHEN sender_user_id = $1 THEN $2 ELSE sender_user_id END, receiver_user_id = CASE WHEN receiver_user_id = $1 THEN $2 ELSE receiver_user_id END WHERE sender_user_id = $1 OR receiver_user_id = $1"
+        ).await?;
+
+        pg_client.execute(&stmt, &[&user_id, &Uuid::nil()]).await
+    }
     // async fn read<C: GenericClient>(pg_client: &C, message_id: Uuid) -> Result<Message, tokio_postgres::Error> {
     //     let stmt = pg_client.prepare(
     //         "SELECT id, dialog_id, user_id, content FROM dialog_messages WHERE id = $1"
@@ -8,6 +8,7 @@ use futures::StreamExt;
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
 use serde::Deserialize;
 
+mod auth;
 mod dialog;
 mod postgres;
 
@@ -26,6 +27,7 @@ async fn dialog_send(
     req: HttpRequest,
     pg_pool: web::Data<&'static PostgresPool>,
     mut payload: web::Payload,
+    user: auth::AccessTokenUser,
 ) -> HttpResponse {
     let mut body = web::BytesMut::new();
     while let Some(chunk) = payload.next().await {
@@ -39,7 +41,7 @@ async fn dialog_send(
     #[derive(Deserialize)]
     struct DialogSendPayload {
         message_id: String,
-        message_sender_user_id: String,
+        message_sender_user_id: Option<String>,
         message_receiver_user_id: String,
         text: String,
     }
@@ -57,9 +59,9 @@ async fn dialog_send(
         Err(_err) => return HttpResponse::InternalServerError().json("User id is not specified"),
     };
 
-    let message_sender_user_id = match uuid::Uuid::parse_str(&payload_data.message_sender_user_id) {
-        Ok(uid) => uid,
-        Err(_err) => return HttpResponse::InternalServerError().json("User id is not specified"),
+    let message_sender_user_id = match user.get_acting_user_id(payload_data.message_sender_user_id.as_deref()) {
+        Some(uid) => uid,
+        None => return HttpResponse::InternalServerError().json("User id is not specified"),
     };
 
     let message_receiver_user_id =
@@ -115,6 +117,7 @@ async fn dialog_send_messages_vec(
     req: HttpRequest,
     pg_pool: web::Data<&'static PostgresPool>,
     mut payload: web::Payload,
+    user: auth::AccessTokenUser,
 ) -> HttpResponse {
     let mut body = web::BytesMut::new();
     while let Some(chunk) = payload.next().await {
@@ -138,6 +141,12 @@ async fn dialog_send_messages_vec(
         }
     };
 
+    if let Some(user_id) = user.get_user_id() {
+        if !payload_data.messages.iter().all(|message| message.is_participant(&user_id)) {
+            return HttpResponse::Forbidden().json("Unable to save messages of another user");
+        }
+    }
+
     let pg_client = match pg_pool.get().await {
         Ok(client) => client,
         Err(err) => {
@@ -177,6 +186,7 @@ async fn dialog_list(
     req: HttpRequest,
     pool: web::Data<&'static PostgresPool>,
     mut payload: web::Payload,
+    user: auth::AccessTokenUser,
 ) -> HttpResponse {
     log_request(&req);
 
@@ -191,7 +201,7 @@ async fn dialog_list(
 
     #[derive(Deserialize)]
     struct DialogListPayload {
-        user_id1: String,
+        user_id1: Option<String>,
         user_id2: String,
         offset: Option<usize>,
         limit: Option<usize>,
@@ -205,9 +215,9 @@ async fn dialog_list(
         }
     };
 
-    let user_id1 = match uuid::Uuid::parse_str(&payload_data.user_id1) {
-        Ok(user_id2) => user_id2,
-        Err(_err) => return HttpResponse::InternalServerError().json("User id is not specified"),
+    let user_id1 = match user.get_acting_user_id(payload_data.user_id1.as_deref()) {
+        Some(uid) => uid,
+        None => return HttpResponse::InternalServerError().json("User id is not specified"),
     };
 
     let user_id2 = match uuid::Uuid::parse_str(&payload_data.user_id2) {
@@ -264,6 +274,71 @@ async fn dialog_list(
         })))
 }
 
+// Called by the backend to export the whole message history of the user
+async fn user_messages(
+    req: HttpRequest,
+    pool: web::Data<&'static PostgresPool>,
+    user: auth::AccessTokenUser,
+) -> HttpResponse {
+    log_request(&req);
+
+    // The whole history is returned, so the user comes from a verified token only, never from the body
+    let user_id = match user.get_user_id() {
+        Some(user_id) => user_id,
+        None => return HttpResponse::Forbidden().json("Unable to get messages: access token is required"),
+    };
+
+    let client = match pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("Unable to get postgres client: {:?}", err);
+            return HttpResponse::InternalServerError().json("Unable to get postgres client");
+        }
+    };
+
+    match dialog::Message::list_by_user_id(&**client, user_id).await {
+        Ok(messages) => HttpResponse::Ok().json(messages),
+        Err(err) => {
+            log::debug!("Unable to get messages: {:?}", err);
+            HttpResponse::InternalServerError().json("Unable to get messages")
+        }
+    }
+}
+
+// Called by the backend when an account is deleted, see Message::remove_by_user_id for what is kept
+async fn user_remove(
+    req: HttpRequest,
+    pool: web::Data<&'static PostgresPool>,
+    user: auth::AccessTokenUser,
+) -> HttpResponse {
+    log_request(&req);
+
+    // Only a verified token may name the user here, the id from the body is never trusted
+    let user_id = match user.get_user_id() {
+        Some(user_id) => user_id,
+        None => return HttpResponse::Forbidden().json("Unable to remove messages: access token is required"),
+    };
+
+    let client = match pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("Unable to get postgres client: {:?}", err);
+            return HttpResponse::InternalServerError().json("Unable to get postgres client");
+        }
+    };
+
+    match dialog::Message::remove_by_user_id(&**client, user_id).await {
+        Ok(count) => {
+            log::debug!("Removed user '{}' from {} messages", user_id, count);
+            HttpResponse::Ok().json("ok")
+        }
+        Err(err) => {
+            log::debug!("Unable to remove messages: {:?}", err);
+            HttpResponse::InternalServerError().json("Unable to remove messages")
+        }
+    }
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init();
@@ -311,6 +386,26 @@ async fn main() -> std::io::Result<()> {
                     )
                     .route(web::post().to(dialog_send_messages_vec)),
             )
+            .service(
+                web::resource("/user/messages")
+                    .app_data(web::Data::new(postgres::get_replica_pool_ref()))
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("post"),
+                    )
+                    .route(web::post().to(user_messages)),
+            )
+            .service(
+                web::resource("/user/remove")
+                    .app_data(web::Data::new(postgres::get_master_pool_ref()))
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("post"),
+                    )
+                    .route(web::post().to(user_remove)),
+            )
     })
     .bind_openssl(&http_address, builder)
     .unwrap()
@@ -3,7 +3,7 @@ use tokio_postgres::{config::LoadBalanceHosts, NoTls};
 use tokio_postgres_migration::Migration;
 use tokio::sync::OnceCell;
 
-const SCRIPTS_UP: [(&str, &str); 3] = [(
+const SCRIPTS_UP: [(&str, &str); 4] = [(
     "0001_create_extension-uuid-ossp_up",
     include_str!("../migrations/0001_create_extension-uuid-ossp_up.sql"),
 ),(
@@ -18,6 +18,9 @@ const SCRIPTS_UP: [(&str, &str); 3] = [(
 // ),(
     "0001_create_dialogs_up",
     include_str!("../migrations/0001_create_dialogs_up.sql"),
+),(
+    "0001_alter-dialog-messages-add-time-created_up",
+    include_str!("../migrations/0001_alter-dialog-messages-add-time-created_up.sql"),
 ),];
 
 const SCRIPTS_DOWN: [(&str, &str); 4] = [(
@@ -0,0 +1 @@
+ALTER TABLE dialog_messages DROP COLUMN IF EXISTS sender_user_id, DROP COLUMN IF EXISTS receiver_user_id;
\ No newline at end of file
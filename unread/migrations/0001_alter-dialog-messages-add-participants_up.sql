@@ -0,0 +1 @@
+ALTER TABLE dialog_messages ADD COLUMN IF NOT EXISTS sender_user_id UUID, ADD COLUMN IF NOT EXISTS receiver_user_id UUID;
\ No newline at end of file
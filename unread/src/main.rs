@@ -8,6 +8,7 @@ use futures::StreamExt;
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
 use serde::Deserialize;
 
+mod auth;
 mod dialog;
 mod postgres;
 
@@ -26,6 +27,7 @@ async fn dialog_send(
     req: HttpRequest,
     pg_pool: web::Data<&'static PostgresPool>,
     mut payload: web::Payload,
+    user: auth::AccessTokenUser,
 ) -> HttpResponse {
     let mut body = web::BytesMut::new();
     while let Some(chunk) = payload.next().await {
@@ -38,7 +40,7 @@ async fn dialog_send(
 
     #[derive(Deserialize)]
     struct DialogSendPayload {
-        message_sender_user_id: String,
+        message_sender_user_id: Option<String>,
         message_receiver_user_id: String,
         text: String,
     }
@@ -51,9 +53,9 @@ async fn dialog_send(
         }
     };
 
-    let message_sender_user_id = match uuid::Uuid::parse_str(&payload_data.message_sender_user_id) {
-        Ok(user_id2) => user_id2,
-        Err(_err) => return HttpResponse::InternalServerError().json("User id is not specified"),
+    let message_sender_user_id = match user.get_acting_user_id(payload_data.message_sender_user_id.as_deref()) {
+        Some(uid) => uid,
+        None => return HttpResponse::InternalServerError().json("User id is not specified"),
     };
 
     let message_receiver_user_id =
@@ -108,6 +110,7 @@ async fn dialog_list(
     req: HttpRequest,
     pool: web::Data<&'static PostgresPool>,
     mut payload: web::Payload,
+    user: auth::AccessTokenUser,
 ) -> HttpResponse {
     log_request(&req);
 
@@ -122,7 +125,7 @@ async fn dialog_list(
 
     #[derive(Deserialize)]
     struct DialogListPayload {
-        user_id1: String,
+        user_id1: Option<String>,
         user_id2: String,
         offset: Option<usize>,
         limit: Option<usize>,
@@ -136,9 +139,9 @@ async fn dialog_list(
         }
     };
 
-    let user_id1 = match uuid::Uuid::parse_str(&payload_data.user_id1) {
-        Ok(user_id2) => user_id2,
-        Err(_err) => return HttpResponse::InternalServerError().json("User id is not specified"),
+    let user_id1 = match user.get_acting_user_id(payload_data.user_id1.as_deref()) {
+        Some(uid) => uid,
+        None => return HttpResponse::InternalServerError().json("User id is not specified"),
     };
 
     let user_id2 = match uuid::Uuid::parse_str(&payload_data.user_id2) {
@@ -199,6 +202,7 @@ async fn dialog_count(
     req: HttpRequest,
     pool: web::Data<&'static PostgresPool>,
     mut payload: web::Payload,
+    user: auth::AccessTokenUser,
 ) -> HttpResponse {
     log_request(&req);
 
@@ -213,7 +217,7 @@ async fn dialog_count(
 
     #[derive(Deserialize)]
     struct DialogListPayload {
-        user_id1: String,
+        user_id1: Option<String>,
         user_id2: String,
     }
 
@@ -225,9 +229,9 @@ async fn dialog_count(
         }
     };
 
-    let user_id1 = match uuid::Uuid::parse_str(&payload_data.user_id1) {
-        Ok(uid) => uid,
-        Err(_err) => return HttpResponse::InternalServerError().json("User id is not specified"),
+    let user_id1 = match user.get_acting_user_id(payload_data.user_id1.as_deref()) {
+        Some(uid) => uid,
+        None => return HttpResponse::InternalServerError().json("User id is not specified"),
     };
 
     let user_id2 = match uuid::Uuid::parse_str(&payload_data.user_id2) {
@@ -268,6 +272,7 @@ async fn dialog_messages_get_by_ids(
     req: HttpRequest,
     pool: web::Data<&'static PostgresPool>,
     mut payload: web::Payload,
+    user: auth::AccessTokenUser,
 ) -> HttpResponse {
     log_request(&req);
 
@@ -308,6 +313,13 @@ async fn dialog_messages_get_by_ids(
     };
 
     if let Ok(messages) = dialog::Message::get_by_ids(&**client, ids).await {
+        let messages: Vec<dialog::Message> = match user.get_user_id() {
+            Some(user_id) => messages
+                .into_iter()
+                .filter(|message| message.is_participant(&user_id))
+                .collect(),
+            None => messages,
+        };
         return HttpResponse::Ok().json(messages);
     } else {
         return HttpResponse::InternalServerError().json("Unable to get messages");
@@ -318,6 +330,7 @@ async fn dialog_messages_remove(
     req: HttpRequest,
     pool: web::Data<&'static PostgresPool>,
     mut payload: web::Payload,
+    user: auth::AccessTokenUser,
 ) -> HttpResponse {
     log_request(&req);
 
@@ -356,6 +369,22 @@ async fn dialog_messages_remove(
         }
     };
 
+    // Only messages of the token owner may be removed
+    let ids = match user.get_user_id() {
+        Some(user_id) => match dialog::Message::get_by_ids(&**client, ids).await {
+            Ok(messages) => messages
+                .into_iter()
+                .filter(|message| message.is_participant(&user_id))
+                .map(|message| message.get_id())
+                .collect(),
+            Err(err) => {
+                log::debug!("Unable to get messages: {:?}", err);
+                return HttpResponse::InternalServerError().json("Unable to remove messages");
+            }
+        },
+        None => ids,
+    };
+
     if let Ok(_) = dialog::Message::remove_by_ids(&**client, ids).await {
         return HttpResponse::Ok().json("ok");
     } else {
@@ -363,6 +392,71 @@ async fn dialog_messages_remove(
     }
 }
 
+// Called by the backend to export the whole message history of the user
+async fn user_messages(
+    req: HttpRequest,
+    pool: web::Data<&'static PostgresPool>,
+    user: auth::AccessTokenUser,
+) -> HttpResponse {
+    log_request(&req);
+
+    // The whole history is returned, so the user comes from a verified token only, never from the body
+    let user_id = match user.get_user_id() {
+        Some(user_id) => user_id,
+        None => return HttpResponse::Forbidden().json("Unable to get messages: access token is required"),
+    };
+
+    let client = match pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("Unable to get postgres client: {:?}", err);
+            return HttpResponse::InternalServerError().json("Unable to get postgres client");
+        }
+    };
+
+    match dialog::Message::list_by_user_id(&**client, user_id).await {
+        Ok(messages) => HttpResponse::Ok().json(messages),
+        Err(err) => {
+            log::debug!("Unable to get messages: {:?}", err);
+            HttpResponse::InternalServerError().json("Unable to get messages")
+        }
+    }
+}
+
+// Called by the backend when an account is deleted, see Message::remove_by_user_id for what is kept
+async fn user_remove(
+    req: HttpRequest,
+    pool: web::Data<&'static PostgresPool>,
+    user: auth::AccessTokenUser,
+) -> HttpResponse {
+    log_request(&req);
+
+    // Only a verified token may name the user here, the id from the body is never trusted
+    let user_id = match user.get_user_id() {
+        Some(user_id) => user_id,
+        None => return HttpResponse::Forbidden().json("Unable to remove messages: access token is required"),
+    };
+
+    let client = match pool.get().await {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("Unable to get postgres client: {:?}", err);
+            return HttpResponse::InternalServerError().json("Unable to get postgres client");
+        }
+    };
+
+    match dialog::Message::remove_by_user_id(&**client, user_id).await {
+        Ok(count) => {
+            log::debug!("Removed user '{}' from {} messages", user_id, count);
+            HttpResponse::Ok().json("ok")
+        }
+        Err(err) => {
+            log::debug!("Unable to remove messages: {:?}", err);
+            HttpResponse::InternalServerError().json("Unable to remove messages")
+        }
+    }
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init();
@@ -430,6 +524,26 @@ async fn main() -> std::io::Result<()> {
                     )
                     .route(web::post().to(dialog_messages_remove)),
             )
+            .service(
+                web::resource("/user/messages")
+                    .app_data(web::Data::new(postgres::get_replica_pool_ref()))
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("post"),
+                    )
+                    .route(web::post().to(user_messages)),
+            )
+            .service(
+                web::resource("/user/remove")
+                    .app_data(web::Data::new(postgres::get_master_pool_ref()))
+                    .app_data(
+                        bearer::Config::default()
+                            .realm("Restricted area")
+                            .scope("post"),
+                    )
+                    .route(web::post().to(user_remove)),
+            )
     })
     .bind_openssl(&http_address, builder)
     .unwrap()